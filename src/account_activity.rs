@@ -0,0 +1,216 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::{v1, v2};
+
+pub const ACCOUNT_ACTIVITY_URL: &str = "https://api.twitter.com/1.1/account_activity";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WebhookInfo {
+    pub id: String,
+    pub url: String,
+    pub valid: bool,
+    pub created_timestamp: String,
+}
+
+impl WebhookInfo {
+    fn from_json(json: &Value) -> Self {
+        Self {
+            id: json["id"].as_str().unwrap_or_default().to_owned(),
+            url: json["url"].as_str().unwrap_or_default().to_owned(),
+            valid: json["valid"].as_bool().unwrap_or_default(),
+            created_timestamp: json["created_timestamp"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscriptionCount {
+    pub account_name: String,
+    pub subscriptions_count_all: u64,
+    pub subscriptions_count_direct_messages: u64,
+}
+
+// Account Activity API operations for one environment. Twitter requires user
+// context for registering webhooks and subscribing the authenticating user, and
+// app-only auth for listing, counting and removing subscriptions.
+pub struct AccountActivity<'a> {
+    user: &'a v1::Client,
+    app: &'a v2::Client,
+    env_name: String,
+    base_url: String,
+}
+
+impl<'a> AccountActivity<'a> {
+    pub fn new(user: &'a v1::Client, app: &'a v2::Client, env_name: &str) -> Self {
+        Self {
+            user,
+            app,
+            env_name: env_name.to_owned(),
+            base_url: ACCOUNT_ACTIVITY_URL.to_owned(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    fn env_url(&self, path: &str) -> String {
+        format!("{}/all/{}/{}", self.base_url, self.env_name, path)
+    }
+
+    // Twitter sends a CRC request to the url before answering.
+    pub async fn register_webhook(&self, url: &str) -> Result<WebhookInfo, Error> {
+        let response = self
+            .user
+            .post(&self.env_url("webhooks.json"), &vec![("url", url)], &vec![])
+            .await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        Ok(WebhookInfo::from_json(&json))
+    }
+
+    pub async fn webhooks(&self) -> Result<Vec<WebhookInfo>, Error> {
+        let response = self
+            .app
+            .get(&self.env_url("webhooks.json"), &vec![])
+            .await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        Ok(json
+            .as_array()
+            .map(|webhooks| webhooks.iter().map(WebhookInfo::from_json).collect())
+            .unwrap_or_default())
+    }
+
+    // Also re-enables a webhook that was marked invalid.
+    pub async fn trigger_crc(&self, webhook_id: &str) -> Result<(), Error> {
+        let url = self.env_url(&format!("webhooks/{}.json", webhook_id));
+        crate::check_status(self.user.put(&url, &vec![]).await?).await?;
+        Ok(())
+    }
+
+    pub async fn delete_webhook(&self, webhook_id: &str) -> Result<(), Error> {
+        let url = self.env_url(&format!("webhooks/{}.json", webhook_id));
+        crate::check_status(self.user.delete(&url, &vec![]).await?).await?;
+        Ok(())
+    }
+
+    // Subscribes the user of the v1::Client.
+    pub async fn subscribe(&self) -> Result<(), Error> {
+        let url = self.env_url("subscriptions.json");
+        crate::check_status(self.user.post(&url, &vec![], &vec![]).await?).await?;
+        Ok(())
+    }
+
+    pub async fn is_subscribed(&self) -> Result<bool, Error> {
+        let url = self.env_url("subscriptions.json");
+        let response = self.user.get(&url, &vec![]).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        crate::check_status(response).await?;
+        Ok(true)
+    }
+
+    // User ids subscribed to this environment.
+    pub async fn subscriptions(&self) -> Result<Vec<String>, Error> {
+        let url = self.env_url("subscriptions/list.json");
+        let response = self.app.get(&url, &vec![]).await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        Ok(json["subscriptions"]
+            .as_array()
+            .map(|subscriptions| {
+                subscriptions
+                    .iter()
+                    .filter_map(|it| it["user_id"].as_str().map(|it| it.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    // Counts every environment of the app.
+    pub async fn subscription_count(&self) -> Result<SubscriptionCount, Error> {
+        let url = format!("{}/all/subscriptions/count.json", self.base_url);
+        let response = self.app.get(&url, &vec![]).await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        let count = |key: &str| {
+            json[key]
+                .as_str()
+                .and_then(|it| it.parse().ok())
+                .or_else(|| json[key].as_u64())
+                .unwrap_or_default()
+        };
+        Ok(SubscriptionCount {
+            account_name: json["account_name"].as_str().unwrap_or_default().to_owned(),
+            subscriptions_count_all: count("subscriptions_count_all"),
+            subscriptions_count_direct_messages: count("subscriptions_count_direct_messages"),
+        })
+    }
+
+    pub async fn unsubscribe(&self, user_id: &str) -> Result<(), Error> {
+        let url = self.env_url(&format!("subscriptions/{}.json", user_id));
+        crate::check_status(self.app.delete(&url, &vec![]).await?).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account_activity::AccountActivity;
+    use crate::test_server::{json_response, serve};
+    use crate::*;
+
+    #[tokio::test]
+    async fn test_account_activity() {
+        let (base, requests) = serve(|request| {
+            let line = request.lines().next().unwrap_or_default();
+            if line.starts_with("POST /1.1/account_activity/all/dev/webhooks.json") {
+                json_response(
+                    "200 OK",
+                    &[],
+                    r#"{"id":"1","url":"https://example.com/webhook","valid":true,"created_timestamp":"2016-06-02 23:54:02 +0000"}"#,
+                )
+            } else if line.starts_with("GET /1.1/account_activity/all/dev/subscriptions.json") {
+                json_response("404 Not Found", &[], r#"{"errors":[{"code":34}]}"#)
+            } else if line.starts_with("GET /1.1/account_activity/all/subscriptions/count.json") {
+                json_response(
+                    "200 OK",
+                    &[],
+                    r#"{"account_name":"my-account","subscriptions_count_all":"2","subscriptions_count_direct_messages":"0"}"#,
+                )
+            } else {
+                String::from("HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n")
+            }
+        })
+        .await;
+        let user = v1::Client::new("a", "b", "c", "d", None);
+        let app = v2::Client::new("token", None);
+        let account_activity = AccountActivity::new(&user, &app, "dev")
+            .with_base_url(&format!("{}/1.1/account_activity", base));
+        let webhook = account_activity
+            .register_webhook("https://example.com/webhook")
+            .await
+            .unwrap();
+        assert!(webhook.valid);
+        assert!(!account_activity.is_subscribed().await.unwrap());
+        assert_eq!(
+            2,
+            account_activity
+                .subscription_count()
+                .await
+                .unwrap()
+                .subscriptions_count_all
+        );
+        account_activity.unsubscribe("3").await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("oauth_consumer_key=\"a\""));
+        assert!(
+            requests[3].starts_with("DELETE /1.1/account_activity/all/dev/subscriptions/3.json")
+        );
+        assert!(requests[3].contains("Bearer token"));
+    }
+}
@@ -0,0 +1,283 @@
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlowConsumerPolicy {
+    // Discard the oldest queued event to make room, counted in Lag::dropped.
+    DropOldest,
+    // Cut the subscriber off, it still receives what was already queued.
+    Disconnect,
+    // Wait until the subscriber has room, which slows every other subscriber too.
+    Backpressure,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Lag {
+    pub queued: usize,
+    pub dropped: u64,
+}
+
+struct Queue<T> {
+    items: VecDeque<Arc<T>>,
+    dropped: u64,
+    // No more items will be pushed.
+    finished: bool,
+    disconnected: bool,
+    // The Subscriber was dropped.
+    detached: bool,
+}
+
+struct Slot<T> {
+    id: usize,
+    queue: Mutex<Queue<T>>,
+    readable: Notify,
+    writable: Notify,
+}
+
+impl<T> Slot<T> {
+    fn lag(&self) -> Lag {
+        let queue = self.queue.lock().unwrap();
+        Lag {
+            queued: queue.items.len(),
+            dropped: queue.dropped,
+        }
+    }
+
+    fn finish(&self) {
+        self.queue.lock().unwrap().finished = true;
+        self.readable.notify_one();
+    }
+}
+
+pub struct Subscriber<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> Subscriber<T> {
+    pub fn id(&self) -> usize {
+        self.slot.id
+    }
+
+    // None once the broadcaster is closed or this subscriber was disconnected.
+    pub async fn recv(&self) -> Option<Arc<T>> {
+        loop {
+            {
+                let mut queue = self.slot.queue.lock().unwrap();
+                if let Some(item) = queue.items.pop_front() {
+                    self.slot.writable.notify_one();
+                    return Some(item);
+                }
+                if queue.finished {
+                    return None;
+                }
+            }
+            self.slot.readable.notified().await;
+        }
+    }
+
+    pub fn lag(&self) -> Lag {
+        self.slot.lag()
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.slot.queue.lock().unwrap().disconnected
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Arc<T>> {
+        stream::unfold(self, |subscriber| async move {
+            let item = subscriber.recv().await?;
+            Some((item, subscriber))
+        })
+    }
+}
+
+impl<T> Drop for Subscriber<T> {
+    fn drop(&mut self) {
+        self.slot.queue.lock().unwrap().detached = true;
+        self.slot.writable.notify_one();
+    }
+}
+
+// Shares one stream connection between many consumers, each with its own bounded queue.
+pub struct Broadcaster<T> {
+    slots: Mutex<Vec<Arc<Slot<T>>>>,
+    next_id: Mutex<usize>,
+    // Set by close, later subscribers get an already finished queue.
+    closed: AtomicBool,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+}
+
+impl<T> Broadcaster<T> {
+    pub fn new(capacity: usize, policy: SlowConsumerPolicy) -> Self {
+        Self {
+            slots: Mutex::new(vec![]),
+            next_id: Mutex::new(0),
+            closed: AtomicBool::new(false),
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+
+    pub fn subscribe(&self) -> Subscriber<T> {
+        let mut slots = self.slots.lock().unwrap();
+        let closed = self.closed.load(Ordering::SeqCst);
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let slot = Arc::new(Slot {
+            id,
+            queue: Mutex::new(Queue {
+                items: VecDeque::new(),
+                dropped: 0,
+                finished: closed,
+                disconnected: false,
+                detached: false,
+            }),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        });
+        if !closed {
+            slots.push(slot.clone());
+        }
+        Subscriber { slot }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.slots.lock().unwrap().len()
+    }
+
+    // (subscriber id, lag) for every connected subscriber.
+    pub fn lags(&self) -> Vec<(usize, Lag)> {
+        self.slots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|slot| (slot.id, slot.lag()))
+            .collect()
+    }
+
+    pub async fn send(&self, item: T) {
+        let item = Arc::new(item);
+        let slots = self.slots.lock().unwrap().clone();
+        for slot in slots {
+            loop {
+                {
+                    let mut queue = slot.queue.lock().unwrap();
+                    if queue.detached || queue.finished {
+                        break;
+                    }
+                    if queue.items.len() < self.capacity {
+                        queue.items.push_back(item.clone());
+                        slot.readable.notify_one();
+                        break;
+                    }
+                    match self.policy {
+                        SlowConsumerPolicy::DropOldest => {
+                            queue.items.pop_front();
+                            queue.items.push_back(item.clone());
+                            queue.dropped += 1;
+                            break;
+                        }
+                        SlowConsumerPolicy::Disconnect => {
+                            queue.disconnected = true;
+                            queue.finished = true;
+                            slot.readable.notify_one();
+                            break;
+                        }
+                        SlowConsumerPolicy::Backpressure => {}
+                    }
+                }
+                slot.writable.notified().await;
+            }
+        }
+        self.slots.lock().unwrap().retain(|slot| {
+            let queue = slot.queue.lock().unwrap();
+            !queue.detached && !queue.finished
+        });
+    }
+
+    // Subscribers receive what is queued and then None.
+    pub fn close(&self) {
+        let mut slots = self.slots.lock().unwrap();
+        self.closed.store(true, Ordering::SeqCst);
+        for slot in slots.drain(..) {
+            slot.finish();
+        }
+    }
+
+    // Forwards every item of the stream, then closes.
+    pub async fn run<S: Stream<Item = T>>(&self, stream: S) {
+        futures::pin_mut!(stream);
+        while let Some(item) = stream.next().await {
+            self.send(item).await;
+        }
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let broadcaster = Broadcaster::new(2, SlowConsumerPolicy::DropOldest);
+        let fast = broadcaster.subscribe();
+        let slow = broadcaster.subscribe();
+        for i in 1..=2 {
+            broadcaster.send(i).await;
+            assert_eq!(Some(i), fast.recv().await.map(|it| *it));
+        }
+        broadcaster.run(stream::iter(vec![3, 4])).await;
+        assert_eq!(
+            Lag {
+                queued: 2,
+                dropped: 2
+            },
+            slow.lag()
+        );
+        let items: Vec<i32> = slow.into_stream().map(|it| *it).collect().await;
+        assert_eq!(vec![3, 4], items);
+        assert_eq!(Some(3), fast.recv().await.map(|it| *it));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect() {
+        let broadcaster = Broadcaster::new(1, SlowConsumerPolicy::Disconnect);
+        let subscriber = broadcaster.subscribe();
+        broadcaster.send(1).await;
+        broadcaster.send(2).await;
+        assert_eq!(0, broadcaster.subscriber_count());
+        assert!(subscriber.is_disconnected());
+        assert_eq!(Some(1), subscriber.recv().await.map(|it| *it));
+        assert_eq!(None, subscriber.recv().await);
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let broadcaster = Arc::new(Broadcaster::new(1, SlowConsumerPolicy::Backpressure));
+        let subscriber = broadcaster.subscribe();
+        let sender = broadcaster.clone();
+        let handle = tokio::spawn(async move { sender.run(stream::iter(1..=5)).await });
+        let items: Vec<i32> = subscriber.into_stream().map(|it| *it).collect().await;
+        handle.await.unwrap();
+        assert_eq!(vec![1, 2, 3, 4, 5], items);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_after_close() {
+        let broadcaster = Broadcaster::new(1, SlowConsumerPolicy::DropOldest);
+        broadcaster.run(stream::iter(vec![1])).await;
+        let subscriber = broadcaster.subscribe();
+        assert_eq!(None, subscriber.recv().await);
+        assert_eq!(0, broadcaster.subscriber_count());
+        broadcaster.send(2).await;
+        assert_eq!(None, subscriber.recv().await);
+    }
+}
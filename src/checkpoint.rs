@@ -0,0 +1,200 @@
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+use crate::stream::{StreamEvent, MAX_BACKFILL_MINUTES};
+
+// Ids remembered across reconnects to drop tweets delivered again by backfill.
+const SEEN_CAPACITY: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub tweet_id: u64,
+    pub received_at: SystemTime,
+}
+
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    async fn load(&self) -> Result<Option<Checkpoint>, Error>;
+
+    async fn save(&self, checkpoint: Checkpoint) -> Result<(), Error>;
+}
+
+#[derive(Default)]
+pub struct MemoryCheckpointStore {
+    checkpoint: Mutex<Option<Checkpoint>>,
+}
+
+impl MemoryCheckpointStore {
+    pub fn new(checkpoint: Option<Checkpoint>) -> Self {
+        Self {
+            checkpoint: Mutex::new(checkpoint),
+        }
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for MemoryCheckpointStore {
+    async fn load(&self) -> Result<Option<Checkpoint>, Error> {
+        Ok(*self.checkpoint.lock().unwrap())
+    }
+
+    async fn save(&self, checkpoint: Checkpoint) -> Result<(), Error> {
+        *self.checkpoint.lock().unwrap() = Some(checkpoint);
+        Ok(())
+    }
+}
+
+struct Dedupe {
+    // Tweets up to the stored id were delivered before the restart. Kept until backfill
+    // can no longer replay them, later ones at or below it arrived out of order.
+    floor: Option<(u64, SystemTime)>,
+    // Highest id delivered so far, the one saved as checkpoint.
+    highest: Option<u64>,
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+}
+
+impl Dedupe {
+    fn new(checkpoint: Option<Checkpoint>) -> Self {
+        let window = Duration::from_secs(60 * u64::from(MAX_BACKFILL_MINUTES));
+        Self {
+            floor: checkpoint.map(|it| (it.tweet_id, it.received_at + window)),
+            highest: checkpoint.map(|it| it.tweet_id),
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn is_new(&mut self, id: u64) -> bool {
+        if let Some((floor, until)) = self.floor {
+            if SystemTime::now() >= until {
+                self.floor = None;
+            } else if id <= floor {
+                return false;
+            }
+        }
+        if !self.seen.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.highest = self.highest.max(Some(id));
+        true
+    }
+}
+
+fn tweet_id(json: &Value) -> Option<u64> {
+    json["data"]["id"].as_str()?.parse().ok()
+}
+
+// Drops duplicate tweets and saves the highest delivered id after each delivered one.
+// A failed save is yielded as an error and the stream goes on.
+pub(crate) fn track<'a, S: CheckpointStore>(
+    events: BoxStream<'a, Result<StreamEvent<Value>, Error>>,
+    store: &'a S,
+    checkpoint: Option<Checkpoint>,
+) -> impl Stream<Item = Result<StreamEvent<Value>, Error>> + 'a {
+    stream::unfold(
+        (events, Dedupe::new(checkpoint), None),
+        move |(mut events, mut dedupe, mut pending)| async move {
+            if let Some(event) = pending.take() {
+                return Some((event, (events, dedupe, None)));
+            }
+            loop {
+                let event = events.next().await?;
+                let id = match &event {
+                    Ok(StreamEvent::Message(json)) => tweet_id(json),
+                    _ => None,
+                };
+                if let Some(id) = id {
+                    if !dedupe.is_new(id) {
+                        continue;
+                    }
+                    let checkpoint = Checkpoint {
+                        tweet_id: dedupe.highest.unwrap_or(id),
+                        received_at: SystemTime::now(),
+                    };
+                    if let Err(err) = store.save(checkpoint).await {
+                        pending = Some(event);
+                        return Some((Err(err), (events, dedupe, pending)));
+                    }
+                }
+                return Some((event, (events, dedupe, pending)));
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::{Checkpoint, CheckpointStore, MemoryCheckpointStore};
+    use crate::stream::{StreamEvent, StreamOptions};
+    use crate::test_server::serve;
+    use crate::*;
+    use futures::StreamExt;
+    use std::time::{Duration, SystemTime};
+
+    async fn collect(
+        ids: &'static [&'static str],
+        store: &MemoryCheckpointStore,
+    ) -> (Vec<String>, Vec<String>) {
+        let (base, requests) = serve(move |_| {
+            let body = ids
+                .iter()
+                .map(|id| format!("{{\"data\":{{\"id\":\"{}\"}}}}\r\n", id))
+                .collect::<String>();
+            format!("HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n{}", body)
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/search/stream", base);
+        let options = StreamOptions {
+            max_attempts: Some(0),
+            ..Default::default()
+        };
+        let ids = client
+            .checkpointed_stream(&url, &[], options, store)
+            .filter_map(|it| async move {
+                match it {
+                    Ok(StreamEvent::Message(json)) => json["data"]["id"].as_str().map(String::from),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        let requests = requests.lock().unwrap().clone();
+        (ids, requests)
+    }
+
+    #[tokio::test]
+    async fn test_checkpointed_stream() {
+        let store = MemoryCheckpointStore::new(Some(Checkpoint {
+            tweet_id: 10,
+            received_at: SystemTime::now() - Duration::from_secs(150),
+        }));
+        let (ids, requests) = collect(&["9", "10", "12", "11", "10", "12"], &store).await;
+        assert_eq!(vec!["12", "11"], ids);
+        assert_eq!(12, store.load().await.unwrap().unwrap().tweet_id);
+        assert!(requests[0].contains("backfill_minutes=3"));
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_after_backfill_window() {
+        let store = MemoryCheckpointStore::new(Some(Checkpoint {
+            tweet_id: 10,
+            received_at: SystemTime::now() - Duration::from_secs(600),
+        }));
+        let (ids, _) = collect(&["9", "11", "9"], &store).await;
+        assert_eq!(vec!["9", "11"], ids);
+        assert_eq!(11, store.load().await.unwrap().unwrap().tweet_id);
+    }
+}
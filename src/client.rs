@@ -0,0 +1,107 @@
+use futures::future::BoxFuture;
+use reqwest::{multipart::Form, Response};
+
+use crate::endpoint::AuthType;
+use crate::error::Error;
+
+// Common interface of v1::Client and v2::Client used by paginators and uploaders.
+pub trait ApiClient: Sync {
+    // None when the client does not know what its token is.
+    fn auth_type(&self) -> Option<AuthType>;
+
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+    ) -> BoxFuture<'a, Result<Response, Error>>;
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+        form_options: &'a [(&'a str, &'a str)],
+    ) -> BoxFuture<'a, Result<Response, Error>>;
+
+    fn multipart<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+        data: Form,
+    ) -> BoxFuture<'a, Result<Response, Error>>;
+}
+
+impl ApiClient for crate::v1::Client {
+    fn auth_type(&self) -> Option<AuthType> {
+        Some(AuthType::OAuth1User)
+    }
+
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+    ) -> BoxFuture<'a, Result<Response, Error>> {
+        let query_options = query_options.to_vec();
+        Box::pin(async move { crate::v1::Client::get(self, url, &query_options).await })
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+        form_options: &'a [(&'a str, &'a str)],
+    ) -> BoxFuture<'a, Result<Response, Error>> {
+        let query_options = query_options.to_vec();
+        let form_options = form_options.to_vec();
+        Box::pin(
+            async move { crate::v1::Client::post(self, url, &query_options, &form_options).await },
+        )
+    }
+
+    fn multipart<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+        data: Form,
+    ) -> BoxFuture<'a, Result<Response, Error>> {
+        let query_options = query_options.to_vec();
+        Box::pin(async move { crate::v1::Client::multipart(self, url, &query_options, data).await })
+    }
+}
+
+impl ApiClient for crate::v2::Client {
+    fn auth_type(&self) -> Option<AuthType> {
+        crate::v2::Client::auth_type(self)
+    }
+
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+    ) -> BoxFuture<'a, Result<Response, Error>> {
+        let query_options = query_options.to_vec();
+        Box::pin(async move { crate::v2::Client::get(self, url, &query_options).await })
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+        form_options: &'a [(&'a str, &'a str)],
+    ) -> BoxFuture<'a, Result<Response, Error>> {
+        let query_options = query_options.to_vec();
+        let form_options = form_options.to_vec();
+        Box::pin(
+            async move { crate::v2::Client::post(self, url, &query_options, &form_options).await },
+        )
+    }
+
+    fn multipart<'a>(
+        &'a self,
+        url: &'a str,
+        query_options: &'a [(&'a str, &'a str)],
+        data: Form,
+    ) -> BoxFuture<'a, Result<Response, Error>> {
+        let query_options = query_options.to_vec();
+        Box::pin(async move { crate::v2::Client::multipart(self, url, &query_options, data).await })
+    }
+}
@@ -0,0 +1,229 @@
+use serde_json::Value;
+
+pub const TWEETS_COMPLIANCE_STREAM_URL: &str = "https://api.twitter.com/2/tweets/compliance/stream";
+pub const USERS_COMPLIANCE_STREAM_URL: &str = "https://api.twitter.com/2/users/compliance/stream";
+
+pub const COMPLIANCE_PARTITIONS: u32 = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComplianceTweet {
+    pub id: String,
+    pub author_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComplianceEvent {
+    Delete {
+        tweet: ComplianceTweet,
+        event_at: String,
+    },
+    Withheld {
+        tweet: ComplianceTweet,
+        withheld_in_countries: Vec<String>,
+        event_at: String,
+    },
+    Drop {
+        tweet: ComplianceTweet,
+        event_at: String,
+    },
+    Undrop {
+        tweet: ComplianceTweet,
+        event_at: String,
+    },
+    // Remove geo data from the user's tweets up to up_to_tweet_id.
+    ScrubGeo {
+        user_id: String,
+        up_to_tweet_id: String,
+        event_at: String,
+    },
+    UserProtect {
+        user_id: String,
+        event_at: String,
+    },
+    UserUnprotect {
+        user_id: String,
+        event_at: String,
+    },
+    UserSuspend {
+        user_id: String,
+        event_at: String,
+    },
+    UserUnsuspend {
+        user_id: String,
+        event_at: String,
+    },
+    UserDelete {
+        user_id: String,
+        event_at: String,
+    },
+    UserUndelete {
+        user_id: String,
+        event_at: String,
+    },
+    UserWithheld {
+        user_id: String,
+        withheld_in_countries: Vec<String>,
+        event_at: String,
+    },
+    UserProfileModification {
+        user_id: String,
+        profile_field: String,
+        new_value: String,
+        event_at: String,
+    },
+    Unknown(Value),
+}
+
+fn string_of(json: &Value, key: &str) -> String {
+    json[key].as_str().unwrap_or_default().to_owned()
+}
+
+fn countries_of(json: &Value) -> Vec<String> {
+    json["withheld_in_countries"]
+        .as_array()
+        .map(|countries| {
+            countries
+                .iter()
+                .filter_map(|it| it.as_str().map(|it| it.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ComplianceEvent {
+    // {"data": {"<event name>": {...}}}
+    pub fn from_json(json: Value) -> Self {
+        let (name, event) = match json["data"].as_object().and_then(|it| it.iter().next()) {
+            Some((name, event)) => (name.as_str(), event),
+            None => return Self::Unknown(json),
+        };
+        let tweet = || ComplianceTweet {
+            id: string_of(&event["tweet"], "id"),
+            author_id: string_of(&event["tweet"], "author_id"),
+        };
+        let user_id = || string_of(&event["user"], "id");
+        let event_at = string_of(event, "event_at");
+        match name {
+            "delete" => Self::Delete {
+                tweet: tweet(),
+                event_at,
+            },
+            "withheld" => Self::Withheld {
+                tweet: tweet(),
+                withheld_in_countries: countries_of(event),
+                event_at,
+            },
+            "drop" => Self::Drop {
+                tweet: tweet(),
+                event_at,
+            },
+            "undrop" => Self::Undrop {
+                tweet: tweet(),
+                event_at,
+            },
+            "scrub_geo" => Self::ScrubGeo {
+                user_id: user_id(),
+                up_to_tweet_id: string_of(event, "up_to_tweet_id"),
+                event_at,
+            },
+            "user_protect" => Self::UserProtect {
+                user_id: user_id(),
+                event_at,
+            },
+            "user_unprotect" => Self::UserUnprotect {
+                user_id: user_id(),
+                event_at,
+            },
+            "user_suspend" => Self::UserSuspend {
+                user_id: user_id(),
+                event_at,
+            },
+            "user_unsuspend" => Self::UserUnsuspend {
+                user_id: user_id(),
+                event_at,
+            },
+            "user_delete" => Self::UserDelete {
+                user_id: user_id(),
+                event_at,
+            },
+            "user_undelete" => Self::UserUndelete {
+                user_id: user_id(),
+                event_at,
+            },
+            "user_withheld" => Self::UserWithheld {
+                user_id: user_id(),
+                withheld_in_countries: countries_of(event),
+                event_at,
+            },
+            "user_profile_modification" => Self::UserProfileModification {
+                user_id: user_id(),
+                profile_field: string_of(event, "profile_field"),
+                new_value: string_of(event, "new_value"),
+                event_at,
+            },
+            _ => Self::Unknown(json),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compliance::*;
+    use crate::stream::{StreamEvent, StreamOptions};
+    use crate::test_server::serve;
+    use crate::*;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_json() {
+        let event = ComplianceEvent::from_json(json!({"data": {"scrub_geo": {
+            "user": {"id": "1"}, "up_to_tweet_id": "20", "event_at": "2021-07-06T18:00:00.000Z"
+        }}}));
+        assert_eq!(
+            ComplianceEvent::ScrubGeo {
+                user_id: "1".to_owned(),
+                up_to_tweet_id: "20".to_owned(),
+                event_at: "2021-07-06T18:00:00.000Z".to_owned(),
+            },
+            event
+        );
+        let event = ComplianceEvent::from_json(json!({"data": {"user_profile_modification": {
+            "user": {"id": "1"}, "profile_field": "description", "new_value": "hi", "event_at": "t"
+        }}}));
+        assert!(matches!(
+            event,
+            ComplianceEvent::UserProfileModification { ref profile_field, .. } if profile_field == "description"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_compliance_stream() {
+        let (base, requests) = serve(|_| {
+            String::from("HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n{\"data\":{\"withheld\":{\"tweet\":{\"id\":\"2\",\"author_id\":\"1\"},\"withheld_in_countries\":[\"DE\"],\"event_at\":\"t\"}}}\r\n")
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/compliance/stream", base);
+        let options = StreamOptions {
+            max_attempts: Some(0),
+            ..Default::default()
+        };
+        let events: Vec<_> = client
+            .compliance_stream(&url, 3, &[], options)
+            .collect()
+            .await;
+        match &events[1] {
+            Ok(StreamEvent::Message(ComplianceEvent::Withheld {
+                tweet,
+                withheld_in_countries,
+                ..
+            })) => {
+                assert_eq!("2", tweet.id);
+                assert_eq!(vec!["DE"], *withheld_in_countries);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(requests.lock().unwrap()[0].contains("partition=3"));
+    }
+}
@@ -0,0 +1,753 @@
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuthType {
+    OAuth1User,
+    OAuth2User,
+    AppOnly,
+}
+
+impl AuthType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OAuth1User => "OAuth 1.0a User Context",
+            Self::OAuth2User => "OAuth 2.0 User Context",
+            Self::AppOnly => "OAuth 2.0 App Only",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RateLimit {
+    pub auth_type: AuthType,
+    pub requests: u32,
+    pub window_sec: u64,
+}
+
+#[derive(Debug)]
+pub struct Endpoint {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub auth_types: &'static [AuthType],
+    pub scopes: &'static [&'static str],
+    pub rate_limits: &'static [RateLimit],
+}
+
+impl Endpoint {
+    // Template name such as "GET /2/tweets/:id", usable as a metrics or rate limit key.
+    pub fn name(&self) -> String {
+        format!("{} {}", self.method, self.path)
+    }
+
+    pub fn supports(&self, auth_type: AuthType) -> bool {
+        self.auth_types.contains(&auth_type)
+    }
+
+    pub fn rate_limit(&self, auth_type: AuthType) -> Option<&'static RateLimit> {
+        self.rate_limits.iter().find(|it| it.auth_type == auth_type)
+    }
+
+    pub fn matches(&self, method: &str, url: &str) -> bool {
+        if !self.method.eq_ignore_ascii_case(method) {
+            return false;
+        }
+        let mut actual = path_of(url).split('/');
+        let mut template = self.path.split('/');
+        loop {
+            match (template.next(), actual.next()) {
+                (None, None) => return true,
+                (Some(expected), Some(segment)) => {
+                    if !matches_segment(expected, segment) {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+fn path_of(url: &str) -> &str {
+    let url = url.split(['?', '#']).next().unwrap_or("");
+    match url.find("://") {
+        Some(index) => {
+            let rest = &url[index + 3..];
+            rest.find('/').map(|index| &rest[index..]).unwrap_or("/")
+        }
+        None => url,
+    }
+}
+
+// ":id" matches any segment, ":id.json" any segment ending with ".json".
+fn matches_segment(expected: &str, segment: &str) -> bool {
+    match expected.strip_prefix(':') {
+        Some(parameter) => match parameter.find('.') {
+            Some(index) => {
+                let suffix = &parameter[index..];
+                segment.len() > suffix.len() && segment.ends_with(suffix)
+            }
+            None => !segment.is_empty(),
+        },
+        None => expected == segment,
+    }
+}
+
+pub fn find(method: &str, url: &str) -> Option<&'static Endpoint> {
+    ENDPOINTS.iter().find(|it| it.matches(method, url))
+}
+
+// Falls back to the method and path without the query string for unknown endpoints.
+pub fn name(method: &str, url: &str) -> String {
+    match find(method, url) {
+        Some(endpoint) => endpoint.name(),
+        None => format!("{} {}", method.to_uppercase(), path_of(url)),
+    }
+}
+
+// Unknown endpoints are allowed, granted scopes are only checked when known.
+pub fn check(method: &str, url: &str, auth_type: AuthType, scopes: &[String]) -> Result<(), Error> {
+    let endpoint = match find(method, url) {
+        Some(endpoint) => endpoint,
+        None => return Ok(()),
+    };
+    if !endpoint.supports(auth_type) {
+        return Err(Error::UnsupportedAuthType {
+            endpoint: endpoint.name(),
+            auth_type,
+        });
+    }
+    if auth_type == AuthType::OAuth2User && !scopes.is_empty() {
+        let missing: Vec<String> = endpoint
+            .scopes
+            .iter()
+            .filter(|scope| !scopes.iter().any(|it| it == *scope))
+            .map(|scope| scope.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingScopes {
+                endpoint: endpoint.name(),
+                scopes: missing,
+            });
+        }
+    }
+    Ok(())
+}
+
+const WINDOW_15_MIN: u64 = 15 * 60;
+const WINDOW_3_HOURS: u64 = 3 * 60 * 60;
+const WINDOW_24_HOURS: u64 = 24 * 60 * 60;
+
+const USER: &[AuthType] = &[AuthType::OAuth1User, AuthType::OAuth2User];
+const OAUTH1_USER: &[AuthType] = &[AuthType::OAuth1User];
+const APP_ONLY: &[AuthType] = &[AuthType::AppOnly];
+const ALL: &[AuthType] = &[
+    AuthType::OAuth1User,
+    AuthType::OAuth2User,
+    AuthType::AppOnly,
+];
+const V1_ALL: &[AuthType] = &[AuthType::OAuth1User, AuthType::AppOnly];
+
+const fn limit(auth_type: AuthType, requests: u32, window_sec: u64) -> RateLimit {
+    RateLimit {
+        auth_type,
+        requests,
+        window_sec,
+    }
+}
+
+const fn app_user(app: u32, user: u32) -> [RateLimit; 3] {
+    [
+        limit(AuthType::AppOnly, app, WINDOW_15_MIN),
+        limit(AuthType::OAuth1User, user, WINDOW_15_MIN),
+        limit(AuthType::OAuth2User, user, WINDOW_15_MIN),
+    ]
+}
+
+const fn user(user: u32, window_sec: u64) -> [RateLimit; 2] {
+    [
+        limit(AuthType::OAuth1User, user, window_sec),
+        limit(AuthType::OAuth2User, user, window_sec),
+    ]
+}
+
+const fn app(app: u32) -> [RateLimit; 1] {
+    [limit(AuthType::AppOnly, app, WINDOW_15_MIN)]
+}
+
+const TWEET_READ: &[&str] = &["tweet.read", "users.read"];
+const TWEET_WRITE: &[&str] = &["tweet.read", "tweet.write", "users.read"];
+const FOLLOWS_READ: &[&str] = &["tweet.read", "users.read", "follows.read"];
+const FOLLOWS_WRITE: &[&str] = &["tweet.read", "users.read", "follows.write"];
+const LIKE_READ: &[&str] = &["tweet.read", "users.read", "like.read"];
+const LIKE_WRITE: &[&str] = &["tweet.read", "users.read", "like.write"];
+const BLOCK_READ: &[&str] = &["tweet.read", "users.read", "block.read"];
+const LIST_READ: &[&str] = &["tweet.read", "users.read", "list.read"];
+const DM_READ: &[&str] = &["dm.read", "tweet.read", "users.read"];
+const DM_WRITE: &[&str] = &["dm.read", "dm.write", "tweet.read", "users.read"];
+const MEDIA_WRITE: &[&str] = &["media.write"];
+const NONE: &[&str] = &[];
+
+pub static ENDPOINTS: &[Endpoint] = &[
+    // v2 tweets
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(300, 900),
+    },
+    Endpoint {
+        method: "POST",
+        path: "/2/tweets",
+        auth_types: USER,
+        scopes: TWEET_WRITE,
+        rate_limits: &user(200, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/search/recent",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(450, 180),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/search/all",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(300),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/counts/recent",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(300),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/search/stream",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(50),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/search/stream/rules",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(450),
+    },
+    Endpoint {
+        method: "POST",
+        path: "/2/tweets/search/stream/rules",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(450),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/sample/stream",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(50),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/sample10/stream",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(50),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/firehose/stream",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(50),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/compliance/stream",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(50),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/tweets/:id",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(300, 900),
+    },
+    Endpoint {
+        method: "DELETE",
+        path: "/2/tweets/:id",
+        auth_types: USER,
+        scopes: TWEET_WRITE,
+        rate_limits: &user(50, WINDOW_15_MIN),
+    },
+    // v2 users
+    Endpoint {
+        method: "GET",
+        path: "/2/users/me",
+        auth_types: USER,
+        scopes: TWEET_READ,
+        rate_limits: &user(75, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/compliance/stream",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &app(50),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(300, 900),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/by",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(300, 900),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/by/username/:username",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(300, 900),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(300, 900),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/tweets",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(1500, 900),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/mentions",
+        auth_types: ALL,
+        scopes: TWEET_READ,
+        rate_limits: &app_user(450, 180),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/timelines/reverse_chronological",
+        auth_types: USER,
+        scopes: TWEET_READ,
+        rate_limits: &user(180, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/followers",
+        auth_types: ALL,
+        scopes: FOLLOWS_READ,
+        rate_limits: &app_user(15, 15),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/following",
+        auth_types: ALL,
+        scopes: FOLLOWS_READ,
+        rate_limits: &app_user(15, 15),
+    },
+    Endpoint {
+        method: "POST",
+        path: "/2/users/:id/following",
+        auth_types: USER,
+        scopes: FOLLOWS_WRITE,
+        rate_limits: &user(50, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "DELETE",
+        path: "/2/users/:source_user_id/following/:target_user_id",
+        auth_types: USER,
+        scopes: FOLLOWS_WRITE,
+        rate_limits: &user(50, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/liked_tweets",
+        auth_types: ALL,
+        scopes: LIKE_READ,
+        rate_limits: &app_user(75, 75),
+    },
+    Endpoint {
+        method: "POST",
+        path: "/2/users/:id/likes",
+        auth_types: USER,
+        scopes: LIKE_WRITE,
+        rate_limits: &user(50, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "DELETE",
+        path: "/2/users/:id/likes/:tweet_id",
+        auth_types: USER,
+        scopes: LIKE_WRITE,
+        rate_limits: &user(50, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "GET",
+        path: "/2/users/:id/blocking",
+        auth_types: USER,
+        scopes: BLOCK_READ,
+        rate_limits: &user(15, WINDOW_15_MIN),
+    },
+    // v2 lists
+    Endpoint {
+        method: "GET",
+        path: "/2/lists/:id/members",
+        auth_types: ALL,
+        scopes: LIST_READ,
+        rate_limits: &app_user(900, 900),
+    },
+    // v2 direct messages
+    Endpoint {
+        method: "GET",
+        path: "/2/dm_events",
+        auth_types: USER,
+        scopes: DM_READ,
+        rate_limits: &user(300, WINDOW_15_MIN),
+    },
+    Endpoint {
+        method: "POST",
+        path: "/2/dm_conversations/with/:participant_id/messages",
+        auth_types: USER,
+        scopes: DM_WRITE,
+        rate_limits: &user(200, WINDOW_15_MIN),
+    },
+    // v1.1
+    Endpoint {
+        method: "GET",
+        path: "/1.1/account/verify_credentials.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 75, WINDOW_15_MIN)],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/statuses/home_timeline.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 15, WINDOW_15_MIN)],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/statuses/mentions_timeline.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 75, WINDOW_15_MIN)],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/statuses/user_timeline.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 1500, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 900, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/statuses/show.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 900, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 900, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "POST",
+        path: "/1.1/statuses/update.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 300, WINDOW_3_HOURS)],
+    },
+    Endpoint {
+        method: "POST",
+        path: "/1.1/statuses/destroy/:id.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "POST",
+        path: "/1.1/statuses/filter.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/statuses/sample.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/search/tweets.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 450, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 180, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/followers/ids.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 15, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 15, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/followers/list.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 15, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 15, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/friends/ids.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 15, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 15, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/friends/list.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 15, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 15, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/lists/members.json",
+        auth_types: V1_ALL,
+        scopes: NONE,
+        rate_limits: &[
+            limit(AuthType::AppOnly, 75, WINDOW_15_MIN),
+            limit(AuthType::OAuth1User, 900, WINDOW_15_MIN),
+        ],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/blocks/ids.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 15, WINDOW_15_MIN)],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/blocks/list.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 15, WINDOW_15_MIN)],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/direct_messages/events/list.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 15, WINDOW_15_MIN)],
+    },
+    Endpoint {
+        method: "POST",
+        path: "/1.1/direct_messages/events/new.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[limit(AuthType::OAuth1User, 1000, WINDOW_24_HOURS)],
+    },
+    Endpoint {
+        method: "POST",
+        path: "/1.1/media/upload.json",
+        auth_types: USER,
+        scopes: MEDIA_WRITE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/media/upload.json",
+        auth_types: USER,
+        scopes: MEDIA_WRITE,
+        rate_limits: &[],
+    },
+    // v1.1 account activity
+    Endpoint {
+        method: "POST",
+        path: "/1.1/account_activity/all/:env_name/webhooks.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/account_activity/all/webhooks.json",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/account_activity/all/:env_name/webhooks.json",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "PUT",
+        path: "/1.1/account_activity/all/:env_name/webhooks/:webhook_id.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "DELETE",
+        path: "/1.1/account_activity/all/:env_name/webhooks/:webhook_id.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "POST",
+        path: "/1.1/account_activity/all/:env_name/subscriptions.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/account_activity/all/:env_name/subscriptions.json",
+        auth_types: OAUTH1_USER,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/account_activity/all/:env_name/subscriptions/list.json",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "GET",
+        path: "/1.1/account_activity/all/subscriptions/count.json",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+    Endpoint {
+        method: "DELETE",
+        path: "/1.1/account_activity/all/:env_name/subscriptions/:user_id.json",
+        auth_types: APP_ONLY,
+        scopes: NONE,
+        rate_limits: &[],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let endpoint = find(
+            "GET",
+            "https://api.twitter.com/2/users/123/tweets?max_results=5",
+        );
+        assert_eq!("GET /2/users/:id/tweets", endpoint.unwrap().name());
+        let endpoint = find(
+            "POST",
+            "https://api.twitter.com/1.1/statuses/destroy/240854986559455234.json",
+        );
+        assert_eq!(
+            "POST /1.1/statuses/destroy/:id.json",
+            endpoint.unwrap().name()
+        );
+        assert_eq!(
+            "GET /2/users/me",
+            name("GET", "https://api.twitter.com/2/users/me")
+        );
+        assert_eq!(
+            "GET /2/unknown",
+            name("get", "https://api.twitter.com/2/unknown?a=b")
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let url = "https://api.twitter.com/2/users/me";
+        assert!(check("GET", url, AuthType::AppOnly, &[]).is_err());
+        assert!(check("GET", url, AuthType::OAuth1User, &[]).is_ok());
+        let scopes = vec!["tweet.read".to_owned()];
+        match check("GET", url, AuthType::OAuth2User, &scopes) {
+            Err(Error::MissingScopes { scopes, .. }) => assert_eq!(vec!["users.read"], scopes),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    // find returns the first match, so a literal path must come before a template that
+    // also matches it, such as /2/users/me before /2/users/:id.
+    #[test]
+    fn test_literal_paths_come_first() {
+        for endpoint in ENDPOINTS.iter().filter(|it| !it.path.contains(':')) {
+            let url = format!("https://api.twitter.com{}", endpoint.path);
+            assert_eq!(endpoint.name(), find(endpoint.method, &url).unwrap().name());
+        }
+    }
+
+    #[test]
+    fn test_overlapping_paths() {
+        let cases = [
+            ("GET", "/2/users/me", "GET /2/users/me"),
+            ("GET", "/2/users?ids=1", "GET /2/users"),
+            ("GET", "/2/users/by?usernames=a", "GET /2/users/by"),
+            ("GET", "/2/users/123", "GET /2/users/:id"),
+            (
+                "GET",
+                "/2/tweets/search/stream",
+                "GET /2/tweets/search/stream",
+            ),
+            (
+                "GET",
+                "/2/tweets/sample/stream",
+                "GET /2/tweets/sample/stream",
+            ),
+            ("GET", "/2/tweets/123", "GET /2/tweets/:id"),
+        ];
+        for (method, path, expected) in cases.iter() {
+            let url = format!("https://api.twitter.com{}", path);
+            assert_eq!(*expected, name(method, &url));
+        }
+    }
+}
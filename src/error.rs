@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::endpoint::AuthType;
+use crate::v1::AccessLevel;
+
+#[derive(Debug)]
+pub enum Error {
+    Reqwest(reqwest::Error),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+    Status {
+        status_code: u16,
+        body: String,
+    },
+    SessionStore(String),
+    CheckpointStore(String),
+    UploadStateStore(String),
+    InvalidState,
+    LoginExpired,
+    TokenMismatch,
+    InvalidSignature,
+    InvalidMedia(String),
+    MediaProcessing {
+        media_id: String,
+        code: u64,
+        name: String,
+        message: String,
+    },
+    MediaProcessingTimeout {
+        media_id: String,
+        progress_percent: u64,
+    },
+    // segment_index is the first segment not sent, None while waiting for processing.
+    UploadCancelled {
+        media_id: String,
+        segment_index: Option<u64>,
+    },
+    InsufficientAccess {
+        required: AccessLevel,
+        actual: AccessLevel,
+    },
+    UnsupportedAuthType {
+        endpoint: String,
+        auth_type: AuthType,
+    },
+    MissingScopes {
+        endpoint: String,
+        scopes: Vec<String>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reqwest(err) => write!(f, "reqwest error: {}", err),
+            Self::Json(err) => write!(f, "json error: {}", err),
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::Status { status_code, body } => {
+                write!(f, "unexpected status {}: {}", status_code, body)
+            }
+            Self::SessionStore(message) => write!(f, "session store error: {}", message),
+            Self::CheckpointStore(message) => write!(f, "checkpoint store error: {}", message),
+            Self::UploadStateStore(message) => write!(f, "upload state store error: {}", message),
+            Self::InvalidState => write!(f, "unknown login state"),
+            Self::LoginExpired => write!(f, "login request expired"),
+            Self::TokenMismatch => write!(f, "oauth_token does not match the login session"),
+            Self::InvalidSignature => write!(f, "webhook signature does not match"),
+            Self::InvalidMedia(message) => write!(f, "invalid media: {}", message),
+            Self::MediaProcessing {
+                media_id,
+                name,
+                message,
+                ..
+            } => write!(
+                f,
+                "media {} processing failed {}: {}",
+                media_id, name, message
+            ),
+            Self::MediaProcessingTimeout {
+                media_id,
+                progress_percent,
+            } => write!(
+                f,
+                "media {} processing did not finish in time ({}%)",
+                media_id, progress_percent
+            ),
+            Self::UploadCancelled {
+                media_id,
+                segment_index,
+            } => match segment_index {
+                Some(segment_index) => write!(
+                    f,
+                    "upload of media {} cancelled before segment {}",
+                    media_id, segment_index
+                ),
+                None => write!(f, "upload of media {} cancelled while processing", media_id),
+            },
+            Self::InsufficientAccess { required, actual } => write!(
+                f,
+                "access level {} is required but the token has {}",
+                required.as_str(),
+                actual.as_str()
+            ),
+            Self::UnsupportedAuthType {
+                endpoint,
+                auth_type,
+            } => write!(f, "{} does not support {}", endpoint, auth_type.as_str()),
+            Self::MissingScopes { endpoint, scopes } => {
+                write!(f, "{} requires scopes {}", endpoint, scopes.join(" "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Reqwest(err) => Some(err),
+            Self::Json(err) => Some(err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Reqwest(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
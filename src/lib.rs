@@ -1,10 +1,32 @@
+pub mod account_activity;
+pub mod broadcast;
+pub mod checkpoint;
+pub mod client;
+pub mod compliance;
+pub mod endpoint;
+pub mod error;
+pub mod media;
+pub mod media_info;
 pub mod oauth;
+pub mod oauth1;
+pub mod pagination;
 pub(crate) mod raw;
+pub mod rules;
+pub(crate) mod sign;
+pub mod signin;
+pub mod simulator;
+pub mod stream;
+#[cfg(test)]
+mod test_server;
+pub mod timeline;
+pub mod upload_state;
 pub mod v1;
 pub mod v2;
+pub mod webhook;
 
+pub use error::Error;
 pub use reqwest;
-use reqwest::Client;
+use reqwest::{Client, Response};
 pub use serde_json;
 use std::time::Duration;
 
@@ -27,3 +49,14 @@ pub(crate) fn build_client(timeout_sec: Option<Duration>) -> Client {
     .build()
     .unwrap()
 }
+
+pub(crate) async fn check_status(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    Err(Error::Status {
+        status_code: status.as_u16(),
+        body: response.text().await?,
+    })
+}
@@ -0,0 +1,988 @@
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+use reqwest::multipart::{Form, Part};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+
+use crate::client::ApiClient;
+use crate::error::Error;
+use crate::media_info::{Category, MediaInfo};
+use crate::upload_state::{fingerprint, UploadState, UploadStateStore};
+
+pub const UPLOAD_URL: &str = "https://upload.twitter.com/1.1/media/upload.json";
+
+pub const DEFAULT_SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+pub const MAX_SEGMENT_SIZE: usize = 5 * 1024 * 1024;
+// segment_index must be between 0 and 999.
+pub const MAX_SEGMENTS: u64 = 1000;
+pub const DEFAULT_PROCESSING_TIMEOUT: Duration = Duration::from_secs(600);
+// Lower bound between STATUS calls, check_after_secs can be missing or 0.
+pub const MIN_CHECK_AFTER: Duration = Duration::from_secs(1);
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Init {
+    pub media_id: String,
+    pub expires_after_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessingState {
+    Pending,
+    InProgress,
+    Failed,
+    Succeeded,
+}
+
+impl ProcessingState {
+    fn from_str(state: &str) -> Self {
+        match state {
+            "pending" => Self::Pending,
+            "failed" => Self::Failed,
+            "succeeded" => Self::Succeeded,
+            _ => Self::InProgress,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessingInfo {
+    pub state: ProcessingState,
+    pub check_after_secs: u64,
+    pub progress_percent: u64,
+    // (code, name, message) when state is Failed.
+    pub error: Option<(u64, String, String)>,
+}
+
+impl ProcessingInfo {
+    // None when the media needs no processing.
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let info = json.get("processing_info")?;
+        let error = &info["error"];
+        Some(Self {
+            state: ProcessingState::from_str(info["state"].as_str().unwrap_or_default()),
+            check_after_secs: info["check_after_secs"].as_u64().unwrap_or_default(),
+            progress_percent: info["progress_percent"].as_u64().unwrap_or_default(),
+            error: if error.is_object() {
+                Some((
+                    error["code"].as_u64().unwrap_or_default(),
+                    error["name"].as_str().unwrap_or_default().to_owned(),
+                    error["message"].as_str().unwrap_or_default().to_owned(),
+                ))
+            } else {
+                None
+            },
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Progress {
+    Initialized {
+        media_id: String,
+        total_bytes: u64,
+    },
+    Appended {
+        segment_index: u64,
+        bytes_sent: u64,
+        total_bytes: u64,
+    },
+    Processing {
+        progress_percent: u64,
+    },
+}
+
+type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
+
+// Shared flag checked before every APPEND and STATUS request, the request in
+// flight finishes normally.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// Chunked media/upload with INIT, APPEND and FINALIZE. Works with v1::Client and
+// OAuth 2.0 user context v2::Client (media.write scope).
+pub struct MediaUploader<'a, C: ApiClient> {
+    client: &'a C,
+    url: String,
+    media_type: String,
+    media_category: Option<String>,
+    segment_size: usize,
+    category: Option<Category>,
+    processing_timeout: Duration,
+    on_progress: Option<ProgressCallback>,
+    cancel_token: Option<CancelToken>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    state_store: Option<&'a dyn UploadStateStore>,
+    upload_key: Option<String>,
+}
+
+impl<'a, C: ApiClient> MediaUploader<'a, C> {
+    pub fn new(client: &'a C, media_type: &str) -> Self {
+        Self {
+            client,
+            url: UPLOAD_URL.to_owned(),
+            media_type: media_type.to_owned(),
+            media_category: None,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            category: None,
+            processing_timeout: DEFAULT_PROCESSING_TIMEOUT,
+            on_progress: None,
+            cancel_token: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            state_store: None,
+            upload_key: None,
+        }
+    }
+
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_owned();
+        self
+    }
+
+    // tweet_image, tweet_gif, tweet_video, dm_image, dm_gif, dm_video, amplify_video
+    pub fn with_media_category(mut self, media_category: &str) -> Self {
+        self.media_category = Some(media_category.to_owned());
+        self
+    }
+
+    // Clamped to 1..=MAX_SEGMENT_SIZE.
+    pub fn with_segment_size(mut self, segment_size: usize) -> Self {
+        self.segment_size = segment_size.clamp(1, MAX_SEGMENT_SIZE);
+        self
+    }
+
+    // Sniffs the content and validates it against the category limits before INIT,
+    // media_type and media_category then come from the content.
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    // Overall deadline for STATUS polling after FINALIZE.
+    pub fn with_processing_timeout(mut self, processing_timeout: Duration) -> Self {
+        self.processing_timeout = processing_timeout;
+        self
+    }
+
+    // Called after INIT, every APPEND and every processing_info.
+    pub fn with_on_progress<F>(mut self, on_progress: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    // Same events as with_on_progress, a closed receiver is ignored.
+    pub fn with_progress_sender(self, sender: UnboundedSender<Progress>) -> Self {
+        self.with_on_progress(move |progress| {
+            let _ = sender.send(progress);
+        })
+    }
+
+    pub fn with_cancel_token(mut self, cancel_token: CancelToken) -> Self {
+        self.cancel_token = Some(cancel_token);
+        self
+    }
+
+    // Retries of one APPEND, waiting retry_backoff before the first and doubling it.
+    pub fn with_retry(mut self, max_retries: u32, retry_backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    // Saved after INIT, every APPEND and FINALIZE, cleared once processing ends.
+    pub fn with_state_store(mut self, state_store: &'a dyn UploadStateStore) -> Self {
+        self.state_store = Some(state_store);
+        self
+    }
+
+    // Identifies the source in the saved state, e.g. a path with its modification time.
+    // Without it the first segment is the identity.
+    pub fn with_upload_key(mut self, upload_key: &str) -> Self {
+        self.upload_key = Some(upload_key.to_owned());
+        self
+    }
+
+    fn report(&self, progress: Progress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+
+    fn check_cancelled(&self, media_id: &str, segment_index: Option<u64>) -> Result<(), Error> {
+        match &self.cancel_token {
+            Some(cancel_token) if cancel_token.is_cancelled() => Err(Error::UploadCancelled {
+                media_id: media_id.to_owned(),
+                segment_index,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_segments(&self, total_bytes: u64) -> Result<(), Error> {
+        let segments = total_bytes.div_ceil(self.segment_size as u64);
+        if segments > MAX_SEGMENTS {
+            return Err(Error::InvalidMedia(format!(
+                "{} bytes need {} segments of {} bytes, at most {} are allowed",
+                total_bytes, segments, self.segment_size, MAX_SEGMENTS
+            )));
+        }
+        Ok(())
+    }
+
+    async fn command(&self, form_options: &[(&str, &str)]) -> Result<Value, Error> {
+        let response = self.client.post(&self.url, &[], form_options).await?;
+        Ok(crate::check_status(response).await?.json().await?)
+    }
+
+    pub async fn init(&self, total_bytes: u64) -> Result<Init, Error> {
+        self.init_with(
+            total_bytes,
+            &self.media_type,
+            self.media_category.as_deref(),
+        )
+        .await
+    }
+
+    async fn init_with(
+        &self,
+        total_bytes: u64,
+        media_type: &str,
+        media_category: Option<&str>,
+    ) -> Result<Init, Error> {
+        let total_bytes = total_bytes.to_string();
+        let mut form_options = vec![
+            ("command", "INIT"),
+            ("total_bytes", total_bytes.as_str()),
+            ("media_type", media_type),
+        ];
+        if let Some(media_category) = media_category {
+            form_options.push(("media_category", media_category));
+        }
+        let json = self.command(&form_options).await?;
+        Ok(Init {
+            media_id: json["media_id_string"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned(),
+            expires_after_secs: json["expires_after_secs"].as_u64().unwrap_or_default(),
+        })
+    }
+
+    // The parameters go to the query string so OAuth 1.0a signs them, only media is multipart.
+    pub async fn append(
+        &self,
+        media_id: &str,
+        segment_index: u64,
+        segment: Vec<u8>,
+    ) -> Result<(), Error> {
+        let segment_index = segment_index.to_string();
+        let query_options = [
+            ("command", "APPEND"),
+            ("media_id", media_id),
+            ("segment_index", segment_index.as_str()),
+        ];
+        let data = Form::new().part("media", Part::bytes(segment));
+        let response = self
+            .client
+            .multipart(&self.url, &query_options, data)
+            .await?;
+        crate::check_status(response).await?;
+        Ok(())
+    }
+
+    pub async fn finalize(&self, media_id: &str) -> Result<Value, Error> {
+        self.command(&[("command", "FINALIZE"), ("media_id", media_id)])
+            .await
+    }
+
+    pub async fn status(&self, media_id: &str) -> Result<Option<ProcessingInfo>, Error> {
+        let response = self
+            .client
+            .get(&self.url, &[("command", "STATUS"), ("media_id", media_id)])
+            .await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        Ok(ProcessingInfo::from_json(&json))
+    }
+
+    // Polls STATUS, waiting check_after_secs but at least MIN_CHECK_AFTER between calls,
+    // until processing succeeds, fails or processing_timeout passes.
+    pub async fn wait_for_processing(
+        &self,
+        media_id: &str,
+        info: Option<ProcessingInfo>,
+    ) -> Result<(), Error> {
+        let deadline = Instant::now() + self.processing_timeout;
+        let mut info = info;
+        while let Some(current) = info {
+            self.report(Progress::Processing {
+                progress_percent: current.progress_percent,
+            });
+            match current.state {
+                ProcessingState::Succeeded => return Ok(()),
+                ProcessingState::Failed => {
+                    let (code, name, message) = current.error.unwrap_or_default();
+                    return Err(Error::MediaProcessing {
+                        media_id: media_id.to_owned(),
+                        code,
+                        name,
+                        message,
+                    });
+                }
+                ProcessingState::Pending | ProcessingState::InProgress => {}
+            }
+            let wait = Duration::from_secs(current.check_after_secs).max(MIN_CHECK_AFTER);
+            let wait_until = Instant::now() + wait;
+            if wait_until > deadline {
+                return Err(Error::MediaProcessingTimeout {
+                    media_id: media_id.to_owned(),
+                    progress_percent: current.progress_percent,
+                });
+            }
+            tokio::time::sleep_until(wait_until).await;
+            self.check_cancelled(media_id, None)?;
+            info = self.status(media_id).await?;
+        }
+        Ok(())
+    }
+
+    // Returns media_id_string once the media is ready to attach.
+    pub async fn upload(&self, data: &[u8]) -> Result<String, Error> {
+        let segments = stream::iter(data.chunks(self.segment_size).map(|it| Ok(it.to_vec())));
+        let info = match self.category {
+            Some(_) => Some(MediaInfo::inspect(data)?),
+            None => None,
+        };
+        self.upload_segments(data.len() as u64, segments.boxed(), info)
+            .await
+    }
+
+    pub async fn upload_file<P: AsRef<Path>>(&self, path: P) -> Result<String, Error> {
+        let info = match self.category {
+            Some(_) => Some(MediaInfo::inspect_file(&path).await?),
+            None => None,
+        };
+        let file = tokio::fs::File::open(path).await?;
+        let total_bytes = file.metadata().await?.len();
+        let segments = self.read_segments(file);
+        self.upload_segments(total_bytes, segments, info).await
+    }
+
+    // Reads one segment at a time, total_bytes is required by INIT.
+    pub async fn upload_reader<R>(&self, reader: R, total_bytes: u64) -> Result<String, Error>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let segments = self.read_segments(reader);
+        self.upload_segments(total_bytes, segments, None).await
+    }
+
+    fn read_segments<'b, R>(&self, reader: R) -> BoxStream<'b, Result<Vec<u8>, Error>>
+    where
+        R: AsyncRead + Unpin + Send + 'b,
+    {
+        let segment_size = self.segment_size;
+        let segments = stream::try_unfold(reader, move |mut reader| async move {
+            let mut segment = Vec::with_capacity(segment_size);
+            while segment.len() < segment_size {
+                let size = (&mut reader)
+                    .take((segment_size - segment.len()) as u64)
+                    .read_to_end(&mut segment)
+                    .await?;
+                if size == 0 {
+                    break;
+                }
+            }
+            if segment.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some((segment, reader)))
+            }
+        });
+        segments.boxed()
+    }
+
+    // Regroups chunks of any size into segments, buffering at most one segment.
+    pub async fn upload_stream<S, E>(&self, chunks: S, total_bytes: u64) -> Result<String, Error>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin + Send,
+        Error: From<E>,
+    {
+        let segment_size = self.segment_size;
+        let chunks = chunks.map_err(Error::from);
+        let segments = stream::try_unfold(
+            (chunks, BytesMut::new()),
+            move |(mut chunks, mut buffer)| async move {
+                while buffer.len() < segment_size {
+                    match chunks.try_next().await? {
+                        Some(chunk) => buffer.extend_from_slice(&chunk),
+                        None => break,
+                    }
+                }
+                if buffer.is_empty() {
+                    return Ok(None);
+                }
+                let size = buffer.len().min(segment_size);
+                let segment = buffer.split_to(size).to_vec();
+                Ok(Some((segment, (chunks, buffer))))
+            },
+        );
+        self.upload_segments(total_bytes, segments.boxed(), None)
+            .await
+    }
+
+    // Without info the category check inspects the first segment. With a state store
+    // an unexpired upload of the same size continues after its last saved segment.
+    async fn upload_segments(
+        &self,
+        total_bytes: u64,
+        mut segments: BoxStream<'_, Result<Vec<u8>, Error>>,
+        info: Option<MediaInfo>,
+    ) -> Result<String, Error> {
+        self.check_segments(total_bytes)?;
+        let first = segments.try_next().await?;
+        let (media_type, media_category) = match self.category {
+            Some(category) => {
+                let info = match info {
+                    Some(info) => info,
+                    None => MediaInfo::inspect(first.as_deref().unwrap_or_default())?,
+                };
+                info.validate(category, total_bytes)?;
+                (
+                    info.kind.mime_type(),
+                    Some(category.media_category(info.kind)?),
+                )
+            }
+            None => (self.media_type.as_str(), self.media_category.as_deref()),
+        };
+        let fingerprint = fingerprint(
+            self.upload_key.as_deref(),
+            first.as_deref().unwrap_or_default(),
+        );
+        let resumed = match self.state_store {
+            Some(state_store) => state_store
+                .load()
+                .await?
+                .filter(|it| it.can_resume(total_bytes, self.segment_size, &fingerprint)),
+            None => None,
+        };
+        let mut state = match resumed {
+            Some(state) => state,
+            None => {
+                let init = self
+                    .init_with(total_bytes, media_type, media_category)
+                    .await?;
+                let state = UploadState {
+                    media_id: init.media_id,
+                    total_bytes,
+                    segment_size: self.segment_size,
+                    fingerprint,
+                    next_segment_index: 0,
+                    finalized: false,
+                    expires_after_secs: init.expires_after_secs,
+                    initialized_at: SystemTime::now(),
+                };
+                self.save_state(&state).await?;
+                state
+            }
+        };
+        self.report(Progress::Initialized {
+            media_id: state.media_id.clone(),
+            total_bytes,
+        });
+        let info = if state.finalized {
+            self.status(&state.media_id).await?
+        } else {
+            // Segments sent before a restart are read again but not sent.
+            let mut segments = stream::iter(first.map(Ok)).chain(segments);
+            let mut index = 0;
+            let mut sent = 0;
+            while let Some(segment) = segments.try_next().await? {
+                sent += segment.len() as u64;
+                if sent > total_bytes {
+                    break;
+                }
+                if index >= state.next_segment_index {
+                    self.append_with_retry(&state.media_id, index, segment)
+                        .await?;
+                    state.next_segment_index = index + 1;
+                    self.save_state(&state).await?;
+                    self.report(Progress::Appended {
+                        segment_index: index,
+                        bytes_sent: sent,
+                        total_bytes,
+                    });
+                }
+                index += 1;
+            }
+            if sent != total_bytes {
+                return Err(Error::InvalidMedia(format!(
+                    "read {} bytes from the source, total_bytes is {}",
+                    sent, total_bytes
+                )));
+            }
+            let json = self.finalize(&state.media_id).await?;
+            state.finalized = true;
+            self.save_state(&state).await?;
+            ProcessingInfo::from_json(&json)
+        };
+        let result = self.wait_for_processing(&state.media_id, info).await;
+        if let (Some(state_store), Ok(()) | Err(Error::MediaProcessing { .. })) =
+            (self.state_store, &result)
+        {
+            state_store.clear().await?;
+        }
+        result?;
+        Ok(state.media_id)
+    }
+
+    async fn save_state(&self, state: &UploadState) -> Result<(), Error> {
+        match self.state_store {
+            Some(state_store) => state_store.save(state.clone()).await,
+            None => Ok(()),
+        }
+    }
+
+    // Retries network errors, 429 and 5xx with doubling backoff.
+    async fn append_with_retry(
+        &self,
+        media_id: &str,
+        segment_index: u64,
+        segment: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            self.check_cancelled(media_id, Some(segment_index))?;
+            match self.append(media_id, segment_index, segment.clone()).await {
+                Err(err) if attempt < self.max_retries && is_retryable(&err) => {
+                    tokio::time::sleep(self.retry_backoff * 2u32.pow(attempt.min(16))).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_retryable(err: &Error) -> bool {
+    match err {
+        Error::Reqwest(_) => true,
+        Error::Status { status_code, .. } => *status_code == 429 || *status_code >= 500,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::media::{CancelToken, MediaUploader, Progress, MIN_CHECK_AFTER};
+    use crate::media_info::Category;
+    use crate::test_server::{json_response, serve};
+    use crate::upload_state::{fingerprint, MemoryUploadStateStore, UploadState, UploadStateStore};
+    use crate::*;
+    use bytes::Bytes;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use std::time::{Instant, SystemTime};
+
+    fn upload_server(request: &str) -> String {
+        if request.contains("command=INIT") {
+            json_response(
+                "202 Accepted",
+                &[],
+                r#"{"media_id":710511363345354753,"media_id_string":"710511363345354753","expires_after_secs":86400}"#,
+            )
+        } else if request.contains("command=APPEND") {
+            String::from("HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n")
+        } else {
+            json_response(
+                "201 Created",
+                &[],
+                r#"{"media_id":710511363345354753,"media_id_string":"710511363345354753","size":10,"expires_after_secs":86400}"#,
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload() {
+        let (base, requests) = serve(upload_server).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let media_id = MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_media_category("tweet_video")
+            .with_segment_size(4)
+            .upload(b"0123456789")
+            .await
+            .unwrap();
+        assert_eq!("710511363345354753", media_id);
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(5, requests.len());
+            assert!(requests[0]
+                .contains("total_bytes=10&media_type=video%2Fmp4&media_category=tweet_video"));
+            assert!(requests[3].contains("segment_index=2"));
+            assert!(requests[3].contains("\r\n\r\n89\r\n--"));
+            assert!(requests[4].contains("command=FINALIZE"));
+        }
+
+        let client = v2::Client::new_user_context("token", &["media.write"], None);
+        MediaUploader::new(&client, "image/png")
+            .with_url(&url)
+            .upload(b"png")
+            .await
+            .unwrap();
+        assert!(requests.lock().unwrap()[6].contains("Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn test_too_many_segments() {
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let result = MediaUploader::new(&client, "video/mp4")
+            .with_segment_size(1)
+            .upload(&[0u8; 1001])
+            .await;
+        assert!(matches!(result, Err(Error::InvalidMedia(_))));
+    }
+
+    fn processing_server(states: &'static [&'static str]) -> impl Fn(&str) -> String {
+        let polls = AtomicUsize::new(0);
+        move |request| {
+            if request.contains("command=INIT") || request.contains("command=APPEND") {
+                return upload_server(request);
+            }
+            let index = if request.contains("command=STATUS") {
+                polls.fetch_add(1, Ordering::SeqCst) + 1
+            } else {
+                0
+            };
+            let state = states[index.min(states.len() - 1)];
+            // pending leaves out check_after_secs.
+            let check_after = if state == "pending" {
+                ""
+            } else {
+                r#""check_after_secs":1,"#
+            };
+            let error = if state == "failed" {
+                r#","error":{"code":1,"name":"InvalidMedia","message":"Unsupported video format"}"#
+            } else {
+                ""
+            };
+            json_response(
+                "200 OK",
+                &[],
+                &format!(
+                    r#"{{"media_id_string":"1","processing_info":{{"state":"{}",{}"progress_percent":{}{}}}}}"#,
+                    state,
+                    check_after,
+                    index * 50,
+                    error
+                ),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processing() {
+        let (base, requests) =
+            serve(processing_server(&["pending", "in_progress", "succeeded"])).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let progress = Arc::new(Mutex::new(vec![]));
+        let captured = progress.clone();
+        let started = Instant::now();
+        MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_on_progress(move |progress| {
+                if let Progress::Processing { progress_percent } = progress {
+                    captured.lock().unwrap().push(progress_percent)
+                }
+            })
+            .upload(b"mp4")
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= 2 * MIN_CHECK_AFTER);
+        assert_eq!(vec![0, 50, 100], *progress.lock().unwrap());
+        assert!(requests.lock().unwrap()[4]
+            .starts_with("GET /1.1/media/upload.json?command=STATUS&media_id=710511363345354753"));
+
+        let (base, _) = serve(processing_server(&["pending", "failed"])).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let result = MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .upload(b"mp4")
+            .await;
+        match result {
+            Err(Error::MediaProcessing { name, message, .. }) => {
+                assert_eq!("InvalidMedia", name);
+                assert_eq!("Unsupported video format", message);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let (base, _) = serve(processing_server(&["in_progress"])).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let result = MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_processing_timeout(Duration::from_millis(0))
+            .upload(b"mp4")
+            .await;
+        assert!(matches!(result, Err(Error::MediaProcessingTimeout { .. })));
+    }
+
+    fn appended(requests: &[String]) -> Vec<String> {
+        requests
+            .iter()
+            .filter(|it| it.contains("command=APPEND"))
+            .map(|it| {
+                let body = it.split("\r\n\r\n").nth(2).unwrap_or_default();
+                body.split("\r\n").next().unwrap_or_default().to_owned()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_upload_sources() {
+        let (base, requests) = serve(upload_server).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let uploader = MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_segment_size(4);
+
+        let path = std::env::temp_dir().join(format!("media_{}.mp4", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        uploader.upload_file(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        uploader
+            .upload_reader(&b"0123456789"[..], 10)
+            .await
+            .unwrap();
+        let chunks = futures::stream::iter(
+            ["01", "2345678", "9"]
+                .iter()
+                .map(|it| Ok::<_, Error>(Bytes::from_static(it.as_bytes()))),
+        );
+        uploader.upload_stream(chunks, 10).await.unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(["0123", "4567", "89"].repeat(3), appended(&requests));
+        }
+
+        let result = uploader.upload_reader(&b"01234"[..], 10).await;
+        assert!(matches!(result, Err(Error::InvalidMedia(_))));
+    }
+
+    #[tokio::test]
+    async fn test_category() {
+        let (base, requests) = serve(upload_server).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let uploader = MediaUploader::new(&client, "application/octet-stream")
+            .with_url(&url)
+            .with_category(Category::DirectMessage);
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x02\x80\x00\x00\x01\xe0";
+        uploader
+            .upload_reader(&png[..], png.len() as u64)
+            .await
+            .unwrap();
+        assert!(requests.lock().unwrap()[0]
+            .contains("total_bytes=24&media_type=image%2Fpng&media_category=dm_image"));
+
+        let result = uploader.upload(b"%PDF-1.7").await;
+        assert!(matches!(result, Err(Error::InvalidMedia(_))));
+        let uploader = uploader.with_category(Category::Amplify);
+        let result = uploader.upload_reader(&png[..], png.len() as u64).await;
+        assert!(matches!(result, Err(Error::InvalidMedia(_))));
+        assert_eq!(3, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_category_moov_at_end() {
+        let (base, requests) = serve(upload_server).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        // ftyp, mdat then moov, the first segment has no duration.
+        let mut mp4 = b"\x00\x00\x00\x10ftypisom\x00\x00\x02\x00".to_vec();
+        mp4.extend_from_slice(b"\x00\x00\x00\x30mdat");
+        mp4.extend_from_slice(&[0u8; 40]);
+        mp4.extend_from_slice(b"\x00\x00\x00\x74moov\x00\x00\x00\x6cmvhd");
+        mp4.extend_from_slice(&[0u8; 100]);
+        MediaUploader::new(&client, "application/octet-stream")
+            .with_url(&url)
+            .with_segment_size(32)
+            .with_category(Category::Tweet)
+            .upload_reader(&mp4[..], mp4.len() as u64)
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        assert!(requests[0]
+            .contains("total_bytes=180&media_type=video%2Fmp4&media_category=tweet_video"));
+        assert_eq!(6, appended(&requests).len());
+    }
+
+    #[tokio::test]
+    async fn test_progress_and_cancel() {
+        let (base, requests) = serve(upload_server).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_segment_size(4)
+            .with_progress_sender(sender)
+            .upload(b"0123456789")
+            .await
+            .unwrap();
+        let mut events = vec![];
+        while let Some(progress) = receiver.recv().await {
+            events.push(progress);
+        }
+        assert_eq!(4, events.len());
+        assert_eq!(
+            Progress::Appended {
+                segment_index: 2,
+                bytes_sent: 10,
+                total_bytes: 10
+            },
+            events[3]
+        );
+
+        let cancel_token = CancelToken::new();
+        let cancelled = cancel_token.clone();
+        let result = MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_segment_size(4)
+            .with_cancel_token(cancel_token)
+            .with_on_progress(move |progress| {
+                if let Progress::Appended {
+                    segment_index: 0, ..
+                } = progress
+                {
+                    cancelled.cancel();
+                }
+            })
+            .upload(b"0123456789")
+            .await;
+        match result {
+            Err(Error::UploadCancelled {
+                media_id,
+                segment_index,
+            }) => {
+                assert_eq!("710511363345354753", media_id);
+                assert_eq!(Some(1), segment_index);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(5 + 2, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_resume() {
+        let failed = AtomicBool::new(false);
+        let (base, requests) = serve(move |request| {
+            if request.contains("segment_index=1") && !failed.swap(true, Ordering::SeqCst) {
+                json_response("503 Service Unavailable", &[], "{}")
+            } else {
+                upload_server(request)
+            }
+        })
+        .await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let store = MemoryUploadStateStore::default();
+        MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_segment_size(4)
+            .with_retry(1, Duration::from_millis(1))
+            .with_state_store(&store)
+            .upload(b"0123456789")
+            .await
+            .unwrap();
+        assert_eq!(
+            ["0123", "4567", "4567", "89"].to_vec(),
+            appended(&requests.lock().unwrap())
+        );
+        assert_eq!(None, store.load().await.unwrap());
+
+        requests.lock().unwrap().clear();
+        let store = MemoryUploadStateStore::new(Some(UploadState {
+            media_id: String::from("710511363345354753"),
+            total_bytes: 10,
+            segment_size: 4,
+            fingerprint: fingerprint(None, b"0123"),
+            next_segment_index: 2,
+            finalized: false,
+            expires_after_secs: 86400,
+            initialized_at: SystemTime::now(),
+        }));
+        let media_id = MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_segment_size(4)
+            .with_state_store(&store)
+            .upload(b"0123456789")
+            .await
+            .unwrap();
+        assert_eq!("710511363345354753", media_id);
+        let requests = requests.lock().unwrap();
+        assert_eq!(2, requests.len());
+        assert!(requests[0].contains("segment_index=2"));
+        assert!(requests[1].contains("command=FINALIZE"));
+    }
+
+    #[tokio::test]
+    async fn test_resume_other_source() {
+        let (base, requests) = serve(upload_server).await;
+        let url = format!("{}/1.1/media/upload.json", base);
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let store = MemoryUploadStateStore::new(Some(UploadState {
+            media_id: String::from("1"),
+            total_bytes: 10,
+            segment_size: 4,
+            fingerprint: fingerprint(None, b"0123"),
+            next_segment_index: 2,
+            finalized: false,
+            expires_after_secs: 86400,
+            initialized_at: SystemTime::now(),
+        }));
+        MediaUploader::new(&client, "video/mp4")
+            .with_url(&url)
+            .with_segment_size(4)
+            .with_state_store(&store)
+            .upload(b"abcdefghij")
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("command=INIT"));
+        assert_eq!(["abcd", "efgh", "ij"].to_vec(), appended(&requests));
+    }
+}
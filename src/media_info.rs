@@ -0,0 +1,471 @@
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+
+use crate::error::Error;
+
+// Enough for every image header and a faststart moov of a short video.
+pub const HEAD_SIZE: usize = 64 * 1024;
+const MAX_MOOV_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Jpeg,
+    Png,
+    Webp,
+    Gif,
+    Mp4,
+    Mov,
+}
+
+impl MediaKind {
+    // Detects the format from magic bytes, ignoring file names and declared types.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else if data.len() >= 12 && &data[4..8] == b"ftyp" {
+            if &data[8..12] == b"qt  " {
+                Some(Self::Mov)
+            } else {
+                Some(Self::Mp4)
+            }
+        } else if data.len() >= 8 && matches!(&data[4..8], b"moov" | b"mdat" | b"wide") {
+            Some(Self::Mov)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+            Self::Gif => "image/gif",
+            Self::Mp4 => "video/mp4",
+            Self::Mov => "video/quicktime",
+        }
+    }
+
+    pub fn is_video(&self) -> bool {
+        matches!(self, Self::Mp4 | Self::Mov)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Tweet,
+    DirectMessage,
+    Amplify,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_bytes: u64,
+    pub min_duration: Option<Duration>,
+    pub max_duration: Option<Duration>,
+    pub min_size: (u32, u32),
+    pub max_size: (u32, u32),
+}
+
+const MB: u64 = 1024 * 1024;
+
+impl Category {
+    // media_category sent with INIT.
+    pub fn media_category(&self, kind: MediaKind) -> Result<&'static str, Error> {
+        Ok(match (self, kind) {
+            (Self::Tweet, MediaKind::Gif) => "tweet_gif",
+            (Self::Tweet, kind) if kind.is_video() => "tweet_video",
+            (Self::Tweet, _) => "tweet_image",
+            (Self::DirectMessage, MediaKind::Gif) => "dm_gif",
+            (Self::DirectMessage, kind) if kind.is_video() => "dm_video",
+            (Self::DirectMessage, _) => "dm_image",
+            (Self::Amplify, kind) if kind.is_video() => "amplify_video",
+            (Self::Amplify, kind) => {
+                return Err(Error::InvalidMedia(format!(
+                    "amplify accepts video only, got {}",
+                    kind.mime_type()
+                )))
+            }
+        })
+    }
+
+    pub fn limits(&self, kind: MediaKind) -> Limits {
+        match kind {
+            MediaKind::Gif => Limits {
+                max_bytes: 15 * MB,
+                min_duration: None,
+                max_duration: None,
+                min_size: (4, 4),
+                max_size: (1280, 1080),
+            },
+            kind if kind.is_video() => Limits {
+                max_bytes: 512 * MB,
+                min_duration: Some(Duration::from_millis(500)),
+                max_duration: Some(Duration::from_secs(match self {
+                    Self::Amplify => 600,
+                    _ => 140,
+                })),
+                min_size: (32, 32),
+                max_size: match self {
+                    Self::Amplify => (1920, 1200),
+                    _ => (1280, 1024),
+                },
+            },
+            _ => Limits {
+                max_bytes: 5 * MB,
+                min_duration: None,
+                max_duration: None,
+                min_size: (4, 4),
+                max_size: (8192, 8192),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MediaInfo {
+    pub kind: MediaKind,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration: Option<Duration>,
+}
+
+impl MediaInfo {
+    // Reads what the bytes contain, a video whose moov is not in data has no
+    // duration or dimensions.
+    pub fn inspect(data: &[u8]) -> Result<Self, Error> {
+        let kind = MediaKind::sniff(data).ok_or_else(|| {
+            Error::InvalidMedia(String::from(
+                "unsupported media, expected JPEG, PNG, WEBP, GIF, MP4 or MOV",
+            ))
+        })?;
+        let mut info = Self {
+            kind,
+            width: None,
+            height: None,
+            duration: None,
+        };
+        let size = match kind {
+            MediaKind::Jpeg => jpeg_size(data),
+            MediaKind::Png => be32(data, 16).zip(be32(data, 20)),
+            MediaKind::Gif => le16(data, 6).zip(le16(data, 8)),
+            MediaKind::Webp => webp_size(data),
+            MediaKind::Mp4 | MediaKind::Mov => {
+                let (size, duration) = movie(data);
+                info.duration = duration;
+                size
+            }
+        };
+        if let Some((width, height)) = size {
+            info.width = Some(width);
+            info.height = Some(height);
+        }
+        Ok(info)
+    }
+
+    // Seeks to the moov box of a video instead of reading the whole file.
+    pub async fn inspect_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut head = vec![];
+        (&mut file)
+            .take(HEAD_SIZE as u64)
+            .read_to_end(&mut head)
+            .await?;
+        let info = Self::inspect(&head)?;
+        if !info.kind.is_video() || info.duration.is_some() {
+            return Ok(info);
+        }
+        let length = file.metadata().await?.len();
+        let mut offset = 0;
+        while offset + 8 <= length {
+            let mut header = vec![];
+            file.seek(SeekFrom::Start(offset)).await?;
+            (&mut file).take(16).read_to_end(&mut header).await?;
+            let (name, size) = match box_header(&header, length - offset) {
+                Some((name, size, _)) => (name, size),
+                None => break,
+            };
+            if name == *b"moov" && size <= MAX_MOOV_SIZE {
+                let mut moov = vec![];
+                file.seek(SeekFrom::Start(offset)).await?;
+                (&mut file).take(size).read_to_end(&mut moov).await?;
+                let (size, duration) = movie(&moov);
+                return Ok(Self {
+                    width: size.map(|it| it.0),
+                    height: size.map(|it| it.1),
+                    duration,
+                    ..info
+                });
+            }
+            offset += size;
+        }
+        Ok(info)
+    }
+
+    pub fn validate(&self, category: Category, total_bytes: u64) -> Result<(), Error> {
+        let media_category = category.media_category(self.kind)?;
+        let limits = category.limits(self.kind);
+        if total_bytes > limits.max_bytes {
+            return Err(Error::InvalidMedia(format!(
+                "{} must be at most {} bytes, got {}",
+                media_category, limits.max_bytes, total_bytes
+            )));
+        }
+        // upload_reader and upload_stream only inspect the first segment, a moov at the end
+        // leaves the duration to the STATUS check after FINALIZE.
+        if let Some(duration) = self.duration {
+            let too_short = limits.min_duration.map(|it| duration < it);
+            let too_long = limits.max_duration.map(|it| duration > it);
+            if too_short == Some(true) || too_long == Some(true) {
+                return Err(Error::InvalidMedia(format!(
+                    "{} must be between {:.1}s and {:.1}s long, got {:.1}s",
+                    media_category,
+                    limits.min_duration.unwrap_or_default().as_secs_f64(),
+                    limits.max_duration.unwrap_or_default().as_secs_f64(),
+                    duration.as_secs_f64()
+                )));
+            }
+        }
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            let (min_width, min_height) = limits.min_size;
+            let (max_width, max_height) = limits.max_size;
+            if width < min_width || height < min_height || width > max_width || height > max_height
+            {
+                return Err(Error::InvalidMedia(format!(
+                    "{} must be between {}x{} and {}x{}, got {}x{}",
+                    media_category, min_width, min_height, max_width, max_height, width, height
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]) as u32)
+}
+
+fn le16(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]) as u32)
+}
+
+fn le24(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn be64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(bytes);
+    Some(u64::from_be_bytes(buffer))
+}
+
+// Width and height from the first SOFn marker.
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    while offset + 4 <= data.len() {
+        if data[offset] != 0xFF {
+            return None;
+        }
+        let marker = data[offset + 1];
+        if marker == 0xFF {
+            offset += 1;
+            continue;
+        }
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            return Some((be16(data, offset + 7)?, be16(data, offset + 5)?));
+        }
+        offset += 2 + be16(data, offset + 2)? as usize;
+    }
+    None
+}
+
+fn webp_size(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => Some((le16(data, 26)? & 0x3FFF, le16(data, 28)? & 0x3FFF)),
+        b"VP8L" => {
+            let bits = data.get(21..25)?;
+            let (b0, b1, b2, b3) = (
+                bits[0] as u32,
+                bits[1] as u32,
+                bits[2] as u32,
+                bits[3] as u32,
+            );
+            Some((
+                1 + (((b1 & 0x3F) << 8) | b0),
+                1 + (((b3 & 0x0F) << 10) | (b2 << 2) | ((b1 & 0xC0) >> 6)),
+            ))
+        }
+        b"VP8X" => Some((1 + le24(data, 24)?, 1 + le24(data, 27)?)),
+        _ => None,
+    }
+}
+
+// (name, size of the whole box, header length), remaining bounds size 0 boxes.
+fn box_header(data: &[u8], remaining: u64) -> Option<([u8; 4], u64, usize)> {
+    let mut name = [0u8; 4];
+    name.copy_from_slice(data.get(4..8)?);
+    let (size, header) = match be32(data, 0)? {
+        0 => (remaining, 8),
+        1 => (be64(data, 8)?, 16),
+        size => (size as u64, 8),
+    };
+    if size < header as u64 {
+        return None;
+    }
+    Some((name, size, header))
+}
+
+// Child boxes of an ISO BMFF container, a truncated last box keeps what is there.
+fn children(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut result = vec![];
+    let mut offset = 0;
+    while let Some((name, size, header)) = box_header(&data[offset..], (data.len() - offset) as u64)
+    {
+        let end = (offset as u64 + size).min(data.len() as u64) as usize;
+        result.push((name, &data[(offset + header).min(end)..end]));
+        offset = end;
+        if offset >= data.len() {
+            break;
+        }
+    }
+    result
+}
+
+// Video dimensions from the largest tkhd and duration from mvhd.
+fn movie(data: &[u8]) -> (Option<(u32, u32)>, Option<Duration>) {
+    let moov = match children(data).into_iter().find(|it| it.0 == *b"moov") {
+        Some((_, moov)) => moov,
+        None => return (None, None),
+    };
+    let mut size = None;
+    let mut duration = None;
+    for (name, payload) in children(moov) {
+        if name == *b"mvhd" {
+            let (timescale, length) = if payload.first() == Some(&1) {
+                (be32(payload, 20), be64(payload, 24))
+            } else {
+                (be32(payload, 12), be32(payload, 16).map(|it| it as u64))
+            };
+            if let (Some(timescale), Some(length)) = (timescale, length) {
+                if timescale > 0 {
+                    duration = Some(Duration::from_secs_f64(length as f64 / timescale as f64));
+                }
+            }
+        } else if name == *b"trak" {
+            let tkhd = children(payload).into_iter().find(|it| it.0 == *b"tkhd");
+            if let Some((_, tkhd)) = tkhd {
+                let offset = if tkhd.first() == Some(&1) { 88 } else { 76 };
+                if let (Some(width), Some(height)) = (be32(tkhd, offset), be32(tkhd, offset + 4)) {
+                    let (width, height) = (width >> 16, height >> 16);
+                    if width > 0 && height > 0 && size.is_none_or(|(largest, _)| largest < width) {
+                        size = Some((width, height));
+                    }
+                }
+            }
+        }
+    }
+    (size, duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::media_info::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data
+    }
+
+    fn mp4_box(name: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn mp4(seconds: u32, width: u32, height: u32) -> Vec<u8> {
+        let mut mvhd = vec![0u8; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&(seconds * 1000).to_be_bytes());
+        let mut tkhd = vec![0u8; 84];
+        tkhd[76..80].copy_from_slice(&(width << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(height << 16).to_be_bytes());
+        let trak = mp4_box(b"trak", &mp4_box(b"tkhd", &tkhd));
+        let mut moov = mp4_box(b"mvhd", &mvhd);
+        moov.extend_from_slice(&trak);
+        let mut data = mp4_box(b"ftyp", b"isom\x00\x00\x02\x00");
+        data.extend_from_slice(&mp4_box(b"mdat", &[0u8; 32]));
+        data.extend_from_slice(&mp4_box(b"moov", &moov));
+        data
+    }
+
+    #[test]
+    fn test_inspect() {
+        let info = MediaInfo::inspect(&png(640, 480)).unwrap();
+        assert_eq!(
+            (MediaKind::Png, Some(640), Some(480)),
+            (info.kind, info.width, info.height)
+        );
+        let gif = b"GIF89a\x40\x01\xf0\x00";
+        let info = MediaInfo::inspect(gif).unwrap();
+        assert_eq!(
+            (MediaKind::Gif, Some(320), Some(240)),
+            (info.kind, info.width, info.height)
+        );
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x04\x00\x00\xff\xc0\x00\x11\x08\x01\xe0\x02\x80";
+        let info = MediaInfo::inspect(jpeg).unwrap();
+        assert_eq!(
+            (MediaKind::Jpeg, Some(640), Some(480)),
+            (info.kind, info.width, info.height)
+        );
+        let info = MediaInfo::inspect(&mp4(30, 1280, 720)).unwrap();
+        assert_eq!(MediaKind::Mp4, info.kind);
+        assert_eq!(Some(Duration::from_secs(30)), info.duration);
+        assert_eq!((Some(1280), Some(720)), (info.width, info.height));
+        assert!(matches!(
+            MediaInfo::inspect(b"%PDF-1.7"),
+            Err(Error::InvalidMedia(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let video = MediaInfo::inspect(&mp4(300, 1280, 720)).unwrap();
+        match video.validate(Category::Tweet, 1000) {
+            Err(Error::InvalidMedia(message)) => {
+                assert_eq!(
+                    "tweet_video must be between 0.5s and 140.0s long, got 300.0s",
+                    message
+                )
+            }
+            other => panic!("{:?}", other),
+        }
+        video.validate(Category::Amplify, 1000).unwrap();
+        let video = MediaInfo::inspect(&mp4(300, 1280, 720)[..56]).unwrap();
+        assert_eq!(None, video.duration);
+        video.validate(Category::Tweet, 1000).unwrap();
+        let image = MediaInfo::inspect(&png(640, 480)).unwrap();
+        assert!(image.validate(Category::DirectMessage, 6 * MB).is_err());
+        assert!(image.validate(Category::Amplify, 1000).is_err());
+        let image = MediaInfo::inspect(&png(10000, 480)).unwrap();
+        assert!(image.validate(Category::Tweet, 1000).is_err());
+    }
+}
@@ -134,7 +134,7 @@ pub async fn access_token(
     Ok(parse_oauth_body(response).await)
 }
 
-async fn parse_oauth_body(response: Response) -> HashMap<String, String> {
+pub(crate) async fn parse_oauth_body(response: Response) -> HashMap<String, String> {
     let status = response.status();
     let mut result = HashMap::new();
     match response.text().await {
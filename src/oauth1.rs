@@ -0,0 +1,562 @@
+use reqwest::{multipart::Form, Response};
+use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, RsaPrivateKey};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+use twapi_oauth::encode;
+
+use crate::error::Error;
+use crate::sign::{now, to_header, Signer};
+
+pub struct Provider {
+    pub request_token_url: String,
+    pub authorize_url: String,
+    pub access_token_url: String,
+}
+
+impl Provider {
+    pub fn new(request_token_url: &str, authorize_url: &str, access_token_url: &str) -> Self {
+        Self {
+            request_token_url: request_token_url.to_owned(),
+            authorize_url: authorize_url.to_owned(),
+            access_token_url: access_token_url.to_owned(),
+        }
+    }
+
+    pub fn twitter() -> Self {
+        Self::new(
+            "https://api.twitter.com/oauth/request_token",
+            "https://api.twitter.com/oauth/authorize",
+            "https://api.twitter.com/oauth/access_token",
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SignatureMethod {
+    HmacSha1,
+    HmacSha256,
+    Plaintext,
+    RsaSha1(Box<RsaPrivateKey>),
+}
+
+impl SignatureMethod {
+    // Accepts both PKCS#8 ("BEGIN PRIVATE KEY") and PKCS#1 ("BEGIN RSA PRIVATE KEY") PEM.
+    pub fn rsa_sha1_from_pem(pem: &str) -> Result<Self, rsa::pkcs1::Error> {
+        let private_key = match RsaPrivateKey::from_pkcs8_pem(pem) {
+            Ok(private_key) => private_key,
+            Err(_) => RsaPrivateKey::from_pkcs1_pem(pem)?,
+        };
+        Ok(Self::RsaSha1(Box::new(private_key)))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::HmacSha1 => "HMAC-SHA1",
+            Self::HmacSha256 => "HMAC-SHA256",
+            Self::Plaintext => "PLAINTEXT",
+            Self::RsaSha1(_) => "RSA-SHA1",
+        }
+    }
+
+    pub fn body_hash(&self, body: &[u8]) -> String {
+        match self {
+            Self::HmacSha256 => base64::encode(Sha256::digest(body)),
+            _ => base64::encode(Sha1::digest(body)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transmission {
+    Header,
+    QueryString,
+}
+
+pub struct Client {
+    provider: Provider,
+    consumer_key: String,
+    consumer_secret: String,
+    access_key: String,
+    access_secret: String,
+    signature_method: SignatureMethod,
+    transmission: Transmission,
+    body_hash: bool,
+    timeout_sec: Option<Duration>,
+    time_offset: AtomicI64,
+}
+
+impl Client {
+    pub fn new(
+        provider: Provider,
+        consumer_key: &str,
+        consumer_secret: &str,
+        timeout_sec: Option<Duration>,
+    ) -> Self {
+        Self {
+            provider,
+            consumer_key: consumer_key.to_owned(),
+            consumer_secret: consumer_secret.to_owned(),
+            access_key: String::from(""),
+            access_secret: String::from(""),
+            signature_method: SignatureMethod::HmacSha1,
+            transmission: Transmission::Header,
+            body_hash: false,
+            timeout_sec,
+            time_offset: AtomicI64::new(0),
+        }
+    }
+
+    pub fn with_access_token(mut self, access_key: &str, access_secret: &str) -> Self {
+        self.access_key = access_key.to_owned();
+        self.access_secret = access_secret.to_owned();
+        self
+    }
+
+    pub fn with_signature_method(mut self, signature_method: SignatureMethod) -> Self {
+        self.signature_method = signature_method;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: Transmission) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    // Sign oauth_body_hash for JSON bodies, PLAINTEXT has none.
+    pub fn with_body_hash(mut self, body_hash: bool) -> Self {
+        self.body_hash = body_hash;
+        self
+    }
+
+    pub fn provider(&self) -> &Provider {
+        &self.provider
+    }
+
+    // Seconds added to the local clock for oauth_timestamp, learned from the server Date header.
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed)
+    }
+
+    // Returns the Authorization header value (empty for query string transmission)
+    // and the oauth_* parameters to append to the query string.
+    fn calc_oauth(
+        &self,
+        method: &str,
+        url: &str,
+        oauth_options: &Vec<(&str, &str)>,
+        token_secret: &str,
+        options: &Vec<(&str, &str)>,
+    ) -> (String, Vec<(String, String)>) {
+        let signer = Signer {
+            consumer_key: &self.consumer_key,
+            consumer_secret: &self.consumer_secret,
+            token_secret,
+            signature_method: &self.signature_method,
+        };
+        let parameters = signer.parameters(
+            oauth_options,
+            method,
+            url,
+            options,
+            now() + self.time_offset(),
+        );
+        match self.transmission {
+            Transmission::Header => (to_header(&parameters), vec![]),
+            Transmission::QueryString => (String::from(""), parameters),
+        }
+    }
+
+    fn calc_user_oauth(
+        &self,
+        method: &str,
+        url: &str,
+        extra_oauth_options: &[(&str, &str)],
+        options: &Vec<(&str, &str)>,
+    ) -> (String, Vec<(String, String)>) {
+        let mut oauth_options = extra_oauth_options.to_vec();
+        if !self.access_key.is_empty() {
+            oauth_options.push(("oauth_token", &self.access_key));
+        }
+        self.calc_oauth(method, url, &oauth_options, &self.access_secret, options)
+    }
+
+    // Same retry on code 135 as v1::Client, send signs the request again on each call.
+    async fn execute<F, Fut>(&self, send: F) -> Result<Response, Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<Response, reqwest::Error>>,
+    {
+        let response = send().await?;
+        match crate::v1::check_timestamp(response, &self.time_offset).await? {
+            Some(response) => Ok(response),
+            None => Ok(send().await?),
+        }
+    }
+
+    pub async fn request_token_response(
+        &self,
+        oauth_callback: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Result<Response, Error> {
+        let url = &self.provider.request_token_url;
+        self.execute(|| {
+            let (authorization, oauth_query) = self.calc_oauth(
+                "POST",
+                url,
+                &vec![("oauth_callback", oauth_callback)],
+                "",
+                query_options,
+            );
+            async move {
+                let query_options = merge_options(query_options, &oauth_query);
+                crate::raw::post(
+                    url,
+                    &query_options,
+                    &vec![],
+                    &authorization,
+                    self.timeout_sec,
+                )
+                .await
+            }
+        })
+        .await
+    }
+
+    pub async fn request_token(
+        &self,
+        oauth_callback: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let response = self
+            .request_token_response(oauth_callback, query_options)
+            .await?;
+        Ok(crate::oauth::parse_oauth_body(response).await)
+    }
+
+    pub fn authorize_url(&self, oauth_token: &str) -> String {
+        let separator = if self.provider.authorize_url.contains('?') {
+            "&"
+        } else {
+            "?"
+        };
+        format!(
+            "{}{}oauth_token={}",
+            self.provider.authorize_url,
+            separator,
+            encode(oauth_token)
+        )
+    }
+
+    pub async fn access_token_response(
+        &self,
+        oauth_token: &str,
+        oauth_token_secret: &str,
+        oauth_verifier: &str,
+    ) -> Result<Response, Error> {
+        let url = &self.provider.access_token_url;
+        self.execute(|| {
+            let (authorization, oauth_query) = self.calc_oauth(
+                "POST",
+                url,
+                &vec![
+                    ("oauth_token", oauth_token),
+                    ("oauth_verifier", oauth_verifier),
+                ],
+                oauth_token_secret,
+                &vec![],
+            );
+            async move {
+                let query_options = merge_options(&[], &oauth_query);
+                crate::raw::post(
+                    url,
+                    &query_options,
+                    &vec![],
+                    &authorization,
+                    self.timeout_sec,
+                )
+                .await
+            }
+        })
+        .await
+    }
+
+    pub async fn access_token(
+        &self,
+        oauth_token: &str,
+        oauth_token_secret: &str,
+        oauth_verifier: &str,
+    ) -> Result<HashMap<String, String>, Error> {
+        let response = self
+            .access_token_response(oauth_token, oauth_token_secret, oauth_verifier)
+            .await?;
+        Ok(crate::oauth::parse_oauth_body(response).await)
+    }
+
+    pub async fn get(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Result<Response, Error> {
+        self.execute(|| {
+            let (authorization, oauth_query) = self.calc_user_oauth("GET", url, &[], query_options);
+            async move {
+                let query_options = merge_options(query_options, &oauth_query);
+                crate::raw::get(url, &query_options, &authorization, self.timeout_sec).await
+            }
+        })
+        .await
+    }
+
+    pub async fn post(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        form_options: &Vec<(&str, &str)>,
+    ) -> Result<Response, Error> {
+        let mut merged_options = query_options.clone();
+        for option in form_options {
+            merged_options.push(*option);
+        }
+        self.execute(|| {
+            let (authorization, oauth_query) =
+                self.calc_user_oauth("POST", url, &[], &merged_options);
+            async move {
+                let query_options = merge_options(query_options, &oauth_query);
+                crate::raw::post(
+                    url,
+                    &query_options,
+                    form_options,
+                    &authorization,
+                    self.timeout_sec,
+                )
+                .await
+            }
+        })
+        .await
+    }
+
+    pub async fn json(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        data: &Value,
+    ) -> Result<Response, Error> {
+        let body_hash = match self.signature_method {
+            SignatureMethod::Plaintext => None,
+            _ if self.body_hash => Some(
+                self.signature_method
+                    .body_hash(&serde_json::to_vec(data).unwrap_or_default()),
+            ),
+            _ => None,
+        };
+        let oauth_options = match &body_hash {
+            Some(body_hash) => vec![("oauth_body_hash", body_hash.as_str())],
+            None => vec![],
+        };
+        self.execute(|| {
+            let (authorization, oauth_query) =
+                self.calc_user_oauth("POST", url, &oauth_options, query_options);
+            async move {
+                let query_options = merge_options(query_options, &oauth_query);
+                crate::raw::json(url, &query_options, data, &authorization, self.timeout_sec).await
+            }
+        })
+        .await
+    }
+
+    pub async fn put(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Result<Response, Error> {
+        self.execute(|| {
+            let (authorization, oauth_query) = self.calc_user_oauth("PUT", url, &[], query_options);
+            async move {
+                let query_options = merge_options(query_options, &oauth_query);
+                crate::raw::put(url, &query_options, &authorization, self.timeout_sec).await
+            }
+        })
+        .await
+    }
+
+    pub async fn delete(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Result<Response, Error> {
+        self.execute(|| {
+            let (authorization, oauth_query) =
+                self.calc_user_oauth("DELETE", url, &[], query_options);
+            async move {
+                let query_options = merge_options(query_options, &oauth_query);
+                crate::raw::delete(url, &query_options, &authorization, self.timeout_sec).await
+            }
+        })
+        .await
+    }
+
+    // Form can not be replayed, so multipart only benefits from an offset learned earlier.
+    pub async fn multipart(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        data: Form,
+    ) -> Result<Response, Error> {
+        let (authorization, oauth_query) = self.calc_user_oauth("POST", url, &[], query_options);
+        let query_options = merge_options(query_options, &oauth_query);
+        Ok(
+            crate::raw::multipart(url, &query_options, data, &authorization, self.timeout_sec)
+                .await?,
+        )
+    }
+}
+
+fn merge_options<'a>(
+    query_options: &[(&'a str, &'a str)],
+    oauth_query: &'a [(String, String)],
+) -> Vec<(&'a str, &'a str)> {
+    let mut result = query_options.to_vec();
+    for (key, value) in oauth_query {
+        result.push((key, value));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::oauth1::*;
+    use crate::test_server::{form_response, json_response, serve};
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+
+    fn provider(base: &str) -> Provider {
+        Provider::new(
+            &format!("{}/oauth/request_token", base),
+            &format!("{}/oauth/authorize?lang=ja", base),
+            &format!("{}/oauth/access_token", base),
+        )
+    }
+
+    #[test]
+    fn test_body_hash() {
+        // https://tools.ietf.org/id/draft-eaton-oauth-bodyhash-00.html
+        assert_eq!(
+            "Lve95gjOVATpfV8EL5X4nxwjKHE=",
+            SignatureMethod::HmacSha1.body_hash(b"Hello World!")
+        );
+        assert_eq!(
+            "y7vc0naSNE3l26s6vKukE/sPRTByZ95wgUAVdt8csXY=",
+            SignatureMethod::HmacSha256.body_hash(br#"{"text":"hello"}"#)
+        );
+    }
+
+    #[test]
+    fn test_authorize_url() {
+        let client = Client::new(provider("https://example.com"), "a", "b", None);
+        assert_eq!(
+            "https://example.com/oauth/authorize?lang=ja&oauth_token=t%2F1",
+            client.authorize_url("t/1")
+        );
+        let client = Client::new(Provider::twitter(), "a", "b", None);
+        assert_eq!(
+            "https://api.twitter.com/oauth/authorize?oauth_token=t",
+            client.authorize_url("t")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_flow() {
+        let (base, requests) = serve(|request| {
+            if request.starts_with("POST /oauth/request_token") {
+                form_response("oauth_token=t&oauth_token_secret=s&oauth_callback_confirmed=true")
+            } else {
+                form_response("oauth_token=at&oauth_token_secret=as&user_id=1")
+            }
+        })
+        .await;
+        let client = Client::new(provider(&base), "a", "b", None)
+            .with_signature_method(SignatureMethod::HmacSha256);
+        let result = client.request_token("oob", &vec![]).await.unwrap();
+        assert_eq!("t", result["oauth_token"]);
+        let result = client.access_token("t", "s", "v").await.unwrap();
+        assert_eq!("as", result["oauth_token_secret"]);
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("oauth_callback=\"oob\""));
+        assert!(requests[0].contains("oauth_signature_method=\"HMAC-SHA256\""));
+        assert!(requests[1].starts_with("POST /oauth/access_token"));
+        assert!(requests[1].contains("oauth_verifier=\"v\""));
+    }
+
+    #[tokio::test]
+    async fn test_query_string_transmission() {
+        let (base, requests) = serve(|_| form_response("")).await;
+        let client = Client::new(provider(&base), "a", "b", None)
+            .with_access_token("c", "d")
+            .with_transmission(Transmission::QueryString)
+            .with_body_hash(true);
+        let url = format!("{}/resource", base);
+        client.get(&url, &vec![("q", "x")]).await.unwrap();
+        client
+            .json(&url, &vec![], &json!({"text": "hello"}))
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        for request in requests.iter() {
+            let line = request.lines().next().unwrap();
+            assert!(line.contains("oauth_consumer_key=a"));
+            assert!(line.contains("oauth_token=c"));
+            assert!(line.contains("oauth_signature="));
+            assert!(!request.to_lowercase().contains("\r\nauthorization:"));
+        }
+        assert!(requests[0].starts_with("GET /resource?q=x&"));
+        assert!(requests[1]
+            .lines()
+            .next()
+            .unwrap()
+            .contains("oauth_body_hash=fMRfBMPm9YgfwyOi2N6rEyw4SSI%3D"));
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_has_no_body_hash() {
+        let (base, requests) = serve(|_| form_response("")).await;
+        let client = Client::new(provider(&base), "a", "b", None)
+            .with_signature_method(SignatureMethod::Plaintext)
+            .with_body_hash(true);
+        let url = format!("{}/resource", base);
+        client
+            .json(&url, &vec![], &json!({"text": "hello"}))
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("oauth_signature_method=\"PLAINTEXT\""));
+        assert!(!requests[0].contains("oauth_body_hash"));
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_retry() {
+        let calls = AtomicUsize::new(0);
+        let (base, requests) = serve(move |_| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                json_response(
+                    "401 Unauthorized",
+                    &[("date", "Sun, 06 Nov 2033 08:49:37 GMT")],
+                    r#"{"errors":[{"code":135,"message":"Timestamp out of bounds."}]}"#,
+                )
+            } else {
+                json_response("200 OK", &[], "{}")
+            }
+        })
+        .await;
+        let client = Client::new(provider(&base), "a", "b", None).with_access_token("c", "d");
+        let url = format!("{}/resource", base);
+        let response = client.get(&url, &vec![]).await.unwrap();
+        assert_eq!(200, response.status().as_u16());
+        assert_eq!(2, requests.lock().unwrap().len());
+        assert!(client.time_offset() > 0);
+    }
+}
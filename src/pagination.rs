@@ -0,0 +1,345 @@
+use futures::stream::{self, Stream, StreamExt};
+use reqwest::{Response, StatusCode};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::client::ApiClient;
+use crate::endpoint::{self, AuthType};
+use crate::error::Error;
+use crate::sign::now;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub next_token: Option<String>,
+    pub finished: bool,
+}
+
+#[derive(Debug)]
+pub struct Page {
+    pub json: Value,
+    // Position after this page, persist it to resume later.
+    pub cursor: Cursor,
+}
+
+impl Page {
+    // v2 "data", or the v1.1 cursored "ids", "users" and "lists" arrays.
+    pub fn data(&self) -> Vec<Value> {
+        ["data", "ids", "users", "lists"]
+            .iter()
+            .find_map(|key| self.json[*key].as_array())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+enum Style {
+    // v2 meta.next_token
+    NextToken,
+    // v1.1 next_cursor_str, 0 at the end
+    Cursor,
+}
+
+pub struct Paginator<'a, C: ApiClient> {
+    client: &'a C,
+    url: String,
+    query_options: Vec<(String, String)>,
+    style: Style,
+    token_parameter: String,
+    max_pages: Option<usize>,
+    max_items: Option<usize>,
+    wait_for_rate_limit: bool,
+    cursor: Cursor,
+}
+
+struct State<'a, C: ApiClient> {
+    paginator: Paginator<'a, C>,
+    pages: usize,
+    items: usize,
+    wait: Option<Duration>,
+    failed: bool,
+}
+
+impl<'a, C: ApiClient> Paginator<'a, C> {
+    pub fn new(client: &'a C, url: &str, query_options: &[(&str, &str)]) -> Self {
+        // Search and counts endpoints take next_token, the others pagination_token.
+        let token_parameter = if url.contains("/search/") || url.contains("/counts/") {
+            "next_token"
+        } else {
+            "pagination_token"
+        };
+        Self {
+            client,
+            url: url.to_owned(),
+            query_options: query_options
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            style: Style::NextToken,
+            token_parameter: token_parameter.to_owned(),
+            max_pages: None,
+            max_items: None,
+            wait_for_rate_limit: true,
+            cursor: Cursor::default(),
+        }
+    }
+
+    pub fn new_cursor(client: &'a C, url: &str, query_options: &[(&str, &str)]) -> Self {
+        let mut paginator = Self::new(client, url, query_options);
+        paginator.style = Style::Cursor;
+        paginator.token_parameter = String::from("cursor");
+        paginator
+    }
+
+    pub fn with_token_parameter(mut self, token_parameter: &str) -> Self {
+        self.token_parameter = token_parameter.to_owned();
+        self
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    pub fn with_wait_for_rate_limit(mut self, wait_for_rate_limit: bool) -> Self {
+        self.wait_for_rate_limit = wait_for_rate_limit;
+        self
+    }
+
+    pub fn resume(mut self, cursor: Cursor) -> Self {
+        self.cursor = cursor;
+        self
+    }
+
+    // Returns the page and how long to wait before the next request.
+    async fn fetch(&self) -> Result<(Page, Option<Duration>), Error> {
+        let mut query_options: Vec<(&str, &str)> = self
+            .query_options
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        match (&self.cursor.next_token, &self.style) {
+            (Some(next_token), _) => query_options.push((&self.token_parameter, next_token)),
+            (None, Style::Cursor) => query_options.push((&self.token_parameter, "-1")),
+            (None, Style::NextToken) => {}
+        }
+        let response = get_waiting(
+            self.client,
+            &self.url,
+            &query_options,
+            self.wait_for_rate_limit,
+        )
+        .await?;
+        let wait = if self.wait_for_rate_limit && is_rate_limit_exhausted(&response) {
+            rate_limit_wait(&response, self.client.auth_type(), &self.url)
+        } else {
+            None
+        };
+        let json: Value = crate::check_status(response).await?.json().await?;
+        let next_token = match self.style {
+            Style::NextToken => json["meta"]["next_token"].as_str(),
+            Style::Cursor => json["next_cursor_str"].as_str().filter(|it| *it != "0"),
+        }
+        .map(|it| it.to_owned());
+        let cursor = Cursor {
+            finished: next_token.is_none(),
+            next_token,
+        };
+        Ok((Page { json, cursor }, wait))
+    }
+
+    pub fn pages(self) -> impl Stream<Item = Result<Page, Error>> + 'a {
+        let state = State {
+            paginator: self,
+            pages: 0,
+            items: 0,
+            wait: None,
+            failed: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            let paginator = &state.paginator;
+            if state.failed
+                || paginator.cursor.finished
+                || paginator.max_pages.is_some_and(|it| state.pages >= it)
+                || paginator.max_items.is_some_and(|it| state.items >= it)
+            {
+                return None;
+            }
+            if let Some(wait) = state.wait.take() {
+                tokio::time::sleep(wait).await;
+            }
+            match state.paginator.fetch().await {
+                Ok((page, wait)) => {
+                    state.paginator.cursor = page.cursor.clone();
+                    state.pages += 1;
+                    state.items += page.data().len();
+                    state.wait = wait;
+                    Some((Ok(page), state))
+                }
+                Err(err) => {
+                    state.failed = true;
+                    Some((Err(err), state))
+                }
+            }
+        })
+    }
+
+    pub fn items(self) -> impl Stream<Item = Result<Value, Error>> + 'a {
+        let max_items = self.max_items.unwrap_or(usize::MAX);
+        self.pages()
+            .flat_map(|page| {
+                let items: Vec<Result<Value, Error>> = match page {
+                    Ok(page) => page.data().into_iter().map(Ok).collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                stream::iter(items)
+            })
+            .take(max_items)
+    }
+}
+
+// Retries after x-rate-limit-reset when the request is answered with 429.
+pub(crate) async fn get_waiting<C: ApiClient>(
+    client: &C,
+    url: &str,
+    query_options: &[(&str, &str)],
+    wait_for_rate_limit: bool,
+) -> Result<Response, Error> {
+    loop {
+        let response = client.get(url, query_options).await?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS && wait_for_rate_limit {
+            if let Some(wait) = rate_limit_wait(&response, client.auth_type(), url) {
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+        }
+        return Ok(response);
+    }
+}
+
+fn header_i64(response: &Response, name: &str) -> Option<i64> {
+    response.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
+pub(crate) fn is_rate_limit_exhausted(response: &Response) -> bool {
+    header_i64(response, "x-rate-limit-remaining") == Some(0)
+}
+
+// Until x-rate-limit-reset, or one documented window of the endpoint without the header.
+pub(crate) fn rate_limit_wait(
+    response: &Response,
+    auth_type: Option<AuthType>,
+    url: &str,
+) -> Option<Duration> {
+    if let Some(reset) = header_i64(response, "x-rate-limit-reset") {
+        return Some(Duration::from_secs((reset - now()).max(0) as u64 + 1));
+    }
+    let endpoint = endpoint::find("GET", url)?;
+    let rate_limit = match auth_type {
+        Some(auth_type) => endpoint.rate_limit(auth_type),
+        None => endpoint.rate_limits.first(),
+    }?;
+    Some(Duration::from_secs(rate_limit.window_sec))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_server::{json_response, serve};
+    use crate::*;
+    use futures::StreamExt;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let response = |headers: &[(&str, &str)]| {
+            let mut builder = http::Response::builder().status(429);
+            for (key, value) in headers {
+                builder = builder.header(*key, *value);
+            }
+            reqwest::Response::from(builder.body("").unwrap())
+        };
+        let url = "https://api.twitter.com/2/users/1/followers";
+        let reset = (crate::sign::now() + 59).to_string();
+        let wait = pagination::rate_limit_wait(
+            &response(&[("x-rate-limit-reset", &reset)]),
+            Some(endpoint::AuthType::AppOnly),
+            url,
+        );
+        assert!(wait.unwrap() <= Duration::from_secs(60));
+        let wait =
+            pagination::rate_limit_wait(&response(&[]), Some(endpoint::AuthType::AppOnly), url);
+        assert_eq!(Some(Duration::from_secs(15 * 60)), wait);
+        let url = "https://api.twitter.com/2/unknown";
+        assert_eq!(None, pagination::rate_limit_wait(&response(&[]), None, url));
+    }
+
+    #[tokio::test]
+    async fn test_paginate_items() {
+        let (base, requests) = serve(|request| {
+            let body = if request.contains("pagination_token=p2") {
+                r#"{"data":[{"id":"3"}],"meta":{"result_count":1}}"#
+            } else {
+                r#"{"data":[{"id":"1"},{"id":"2"}],"meta":{"next_token":"p2"}}"#
+            };
+            json_response("200 OK", &[], body)
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/users/1/followers", base);
+        let items: Vec<Value> = client
+            .paginate(&url, &vec![("max_results", "2")])
+            .items()
+            .map(|it| it.unwrap())
+            .collect()
+            .await;
+        let ids: Vec<&str> = items.iter().map(|it| it["id"].as_str().unwrap()).collect();
+        assert_eq!(vec!["1", "2", "3"], ids);
+        assert_eq!(2, requests.lock().unwrap().len());
+
+        let pages: Vec<_> = client
+            .paginate(&url, &vec![])
+            .with_max_pages(1)
+            .pages()
+            .collect()
+            .await;
+        let cursor = pages[0].as_ref().unwrap().cursor.clone();
+        assert_eq!(Some("p2".to_owned()), cursor.next_token);
+        let items: Vec<_> = client
+            .paginate(&url, &vec![])
+            .resume(cursor)
+            .items()
+            .collect()
+            .await;
+        assert_eq!(1, items.len());
+    }
+
+    #[tokio::test]
+    async fn test_paginate_cursor() {
+        let (base, requests) = serve(|request| {
+            let body = if request.contains("cursor=-1") {
+                r#"{"ids":[1,2],"next_cursor_str":"1374004777531007833","previous_cursor_str":"0"}"#
+            } else {
+                r#"{"ids":[3],"next_cursor_str":"0","previous_cursor_str":"-1374004777531007833"}"#
+            };
+            json_response("200 OK", &[], body)
+        })
+        .await;
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/followers/ids.json", base);
+        let pages: Vec<_> = client
+            .paginate_cursor(&url, &vec![("screen_name", "twitterdev")])
+            .pages()
+            .map(|it| it.unwrap())
+            .collect()
+            .await;
+        assert_eq!(2, pages.len());
+        assert_eq!(vec![serde_json::json!(3)], pages[1].data());
+        assert!(pages[1].cursor.finished);
+        assert!(requests.lock().unwrap()[1].contains("cursor=1374004777531007833"));
+    }
+}
@@ -1,4 +1,4 @@
-use reqwest::{multipart::Form, Error, Response};
+use reqwest::{multipart::Form, Error, RequestBuilder, Response};
 use serde_json::Value;
 use std::time::Duration;
 use twapi_oauth::encode;
@@ -15,6 +15,16 @@ fn make_query(list: &Vec<(&str, &str)>, separator: &str) -> String {
     }
     result
 }
+
+// OAuth1 query string transmission signs without an Authorization header.
+fn authorize(builder: RequestBuilder, authorization: &str) -> RequestBuilder {
+    if authorization.is_empty() {
+        builder
+    } else {
+        builder.header("Authorization", authorization)
+    }
+}
+
 pub(crate) async fn get(
     url: &str,
     query_options: &Vec<(&str, &str)>,
@@ -27,11 +37,7 @@ pub(crate) async fn get(
         url.to_owned()
     };
     let client = build_client(timeout_sec);
-    client
-        .get(&url)
-        .header("Authorization", authorization)
-        .send()
-        .await
+    authorize(client.get(&url), authorization).send().await
 }
 
 pub(crate) async fn post(
@@ -42,9 +48,7 @@ pub(crate) async fn post(
     timeout_sec: Option<Duration>,
 ) -> Result<Response, Error> {
     let client = build_client(timeout_sec);
-    client
-        .post(url)
-        .header("Authorization", authorization)
+    authorize(client.post(url), authorization)
         .header(
             "Content-Type",
             "application/x-www-form-urlencoded;charset=UTF-8",
@@ -63,9 +67,7 @@ pub(crate) async fn json(
     timeout_sec: Option<Duration>,
 ) -> Result<Response, Error> {
     let client = build_client(timeout_sec);
-    client
-        .post(url)
-        .header("Authorization", authorization)
+    authorize(client.post(url), authorization)
         .header("Content-Type", "application/json")
         .query(query_options)
         .json(&data)
@@ -80,9 +82,7 @@ pub(crate) async fn put(
     timeout_sec: Option<Duration>,
 ) -> Result<Response, Error> {
     let client = build_client(timeout_sec);
-    client
-        .put(url)
-        .header("Authorization", authorization)
+    authorize(client.put(url), authorization)
         .query(query_options)
         .send()
         .await
@@ -95,9 +95,7 @@ pub(crate) async fn delete(
     timeout_sec: Option<Duration>,
 ) -> Result<Response, Error> {
     let client = build_client(timeout_sec);
-    client
-        .delete(url)
-        .header("Authorization", authorization)
+    authorize(client.delete(url), authorization)
         .query(query_options)
         .send()
         .await
@@ -111,9 +109,7 @@ pub(crate) async fn multipart(
     timeout_sec: Option<Duration>,
 ) -> Result<Response, Error> {
     let client = build_client(timeout_sec);
-    client
-        .post(url)
-        .header("Authorization", authorization)
+    authorize(client.post(url), authorization)
         .query(query_options)
         .multipart(data)
         .send()
@@ -0,0 +1,335 @@
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::error::Error;
+use crate::v2::Client;
+
+pub const RULES_URL: &str = "https://api.twitter.com/2/tweets/search/stream/rules";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub id: Option<String>,
+    pub value: String,
+    pub tag: Option<String>,
+}
+
+impl Rule {
+    pub fn new(value: &str, tag: Option<&str>) -> Self {
+        Self {
+            id: None,
+            value: value.to_owned(),
+            tag: tag.map(|it| it.to_owned()),
+        }
+    }
+
+    fn from_json(json: &Value) -> Self {
+        Self {
+            id: json["id"].as_str().map(|it| it.to_owned()),
+            value: json["value"].as_str().unwrap_or_default().to_owned(),
+            tag: json["tag"].as_str().map(|it| it.to_owned()),
+        }
+    }
+
+    fn key(&self) -> (&str, Option<&str>) {
+        (&self.value, self.tag.as_deref())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleError {
+    pub id: Option<String>,
+    pub value: Option<String>,
+    pub title: String,
+    pub details: Vec<String>,
+}
+
+impl RuleError {
+    fn from_json(json: &Value) -> Self {
+        Self {
+            id: json["id"].as_str().map(|it| it.to_owned()),
+            value: json["value"].as_str().map(|it| it.to_owned()),
+            title: json["title"].as_str().unwrap_or_default().to_owned(),
+            details: json["details"]
+                .as_array()
+                .map(|details| {
+                    details
+                        .iter()
+                        .filter_map(|it| it.as_str().map(|it| it.to_owned()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RulesResponse {
+    pub rules: Vec<Rule>,
+    pub errors: Vec<RuleError>,
+    // meta.summary (created, not_created, valid, invalid, deleted, not_deleted)
+    pub summary: Value,
+}
+
+impl RulesResponse {
+    fn from_json(json: &Value) -> Self {
+        let list = |key: &str| json[key].as_array().cloned().unwrap_or_default();
+        Self {
+            rules: list("data").iter().map(Rule::from_json).collect(),
+            errors: list("errors").iter().map(RuleError::from_json).collect(),
+            summary: json["meta"]["summary"].clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncResult {
+    pub added: Vec<Rule>,
+    pub deleted: Vec<Rule>,
+    pub unchanged: Vec<Rule>,
+    pub errors: Vec<RuleError>,
+}
+
+pub struct StreamRules<'a> {
+    client: &'a Client,
+    url: String,
+}
+
+impl<'a> StreamRules<'a> {
+    pub fn new(client: &'a Client) -> Self {
+        Self {
+            client,
+            url: RULES_URL.to_owned(),
+        }
+    }
+
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_owned();
+        self
+    }
+
+    pub async fn list(&self) -> Result<Vec<Rule>, Error> {
+        let response = self.client.get(&self.url, &vec![]).await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        Ok(RulesResponse::from_json(&json).rules)
+    }
+
+    async fn post(&self, data: &Value, dry_run: bool) -> Result<RulesResponse, Error> {
+        let query_options = if dry_run {
+            vec![("dry_run", "true")]
+        } else {
+            vec![]
+        };
+        let response = self.client.json(&self.url, &query_options, data).await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        Ok(RulesResponse::from_json(&json))
+    }
+
+    pub async fn add(&self, rules: &[Rule], dry_run: bool) -> Result<RulesResponse, Error> {
+        let add: Vec<Value> = rules
+            .iter()
+            .map(|rule| match &rule.tag {
+                Some(tag) => json!({"value": rule.value, "tag": tag}),
+                None => json!({"value": rule.value}),
+            })
+            .collect();
+        self.post(&json!({ "add": add }), dry_run).await
+    }
+
+    pub async fn delete(&self, ids: &[&str], dry_run: bool) -> Result<RulesResponse, Error> {
+        self.post(&json!({"delete": {"ids": ids}}), dry_run).await
+    }
+
+    // Adds missing (value, tag) pairs before deleting stale ones so matching never has a gap.
+    // Values must be unique, so a value whose tag changes is deleted first and added back.
+    pub async fn sync_rules(&self, desired: &[Rule], dry_run: bool) -> Result<SyncResult, Error> {
+        let current = self.list().await?;
+        let desired_keys: HashSet<_> = desired.iter().map(|it| it.key()).collect();
+        let current_keys: HashSet<_> = current.iter().map(|it| it.key()).collect();
+        let mut result = SyncResult::default();
+
+        let mut seen = HashSet::new();
+        let to_add: Vec<Rule> = desired
+            .iter()
+            .filter(|it| !current_keys.contains(&it.key()) && seen.insert(it.key()))
+            .cloned()
+            .collect();
+        let add_values: HashSet<&str> = to_add.iter().map(|it| it.value.as_str()).collect();
+        let (stale, unchanged): (Vec<Rule>, Vec<Rule>) = current
+            .into_iter()
+            .partition(|it| !desired_keys.contains(&it.key()));
+        result.unchanged = unchanged;
+        let (retagged, stale): (Vec<Rule>, Vec<Rule>) = stale
+            .into_iter()
+            .partition(|it| add_values.contains(it.value.as_str()));
+        self.delete_rules(retagged, dry_run, &mut result).await?;
+
+        let mut failed: HashSet<String> = HashSet::new();
+        if !to_add.is_empty() {
+            let response = self.add(&to_add, dry_run).await?;
+            failed = response
+                .errors
+                .iter()
+                .filter_map(|it| it.value.clone())
+                .collect();
+            result.added = if dry_run {
+                to_add
+                    .into_iter()
+                    .filter(|it| !failed.contains(&it.value))
+                    .collect()
+            } else {
+                response.rules
+            };
+            result.errors.extend(response.errors);
+        }
+
+        // A rule whose replacement failed to add stays.
+        let stale = stale
+            .into_iter()
+            .filter(|it| !failed.contains(&it.value))
+            .collect();
+        self.delete_rules(stale, dry_run, &mut result).await?;
+        Ok(result)
+    }
+
+    async fn delete_rules(
+        &self,
+        rules: Vec<Rule>,
+        dry_run: bool,
+        result: &mut SyncResult,
+    ) -> Result<(), Error> {
+        let ids: Vec<&str> = rules.iter().filter_map(|it| it.id.as_deref()).collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let response = self.delete(&ids, dry_run).await?;
+        let failed: HashSet<&str> = response
+            .errors
+            .iter()
+            .filter_map(|it| it.id.as_deref())
+            .collect();
+        result.deleted.extend(
+            rules
+                .into_iter()
+                .filter(|it| !it.id.as_deref().is_some_and(|id| failed.contains(id))),
+        );
+        result.errors.extend(response.errors);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rules::Rule;
+    use crate::test_server::{json_response, serve};
+    use crate::*;
+
+    #[tokio::test]
+    async fn test_sync() {
+        let (base, requests) = serve(|request| {
+            let body = if request.starts_with("GET") {
+                r#"{"data":[{"id":"1","value":"cat has:images","tag":"cats"},{"id":"2","value":"dog"}]}"#
+            } else if request.contains("\"add\"") {
+                r#"{"data":[{"id":"3","value":"bird","tag":"birds"}],"errors":[{"value":"(","title":"Invalid Rule","details":["Unmatched parenthesis."]}],"meta":{"summary":{"created":1,"not_created":1}}}"#
+            } else {
+                r#"{"meta":{"summary":{"deleted":1,"not_deleted":0}}}"#
+            };
+            json_response("200 OK", &[], body)
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/search/stream/rules", base);
+        let desired = vec![
+            Rule::new("cat has:images", Some("cats")),
+            Rule::new("bird", Some("birds")),
+            Rule::new("(", None),
+        ];
+        let result = client
+            .stream_rules()
+            .with_url(&url)
+            .sync_rules(&desired, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["bird"],
+            result
+                .added
+                .iter()
+                .map(|it| it.value.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("2".to_owned()), result.deleted[0].id);
+        assert_eq!(1, result.unchanged.len());
+        assert_eq!(vec!["Unmatched parenthesis."], result.errors[0].details);
+        let requests = requests.lock().unwrap();
+        assert_eq!(3, requests.len());
+        assert!(requests[2].contains(r#"{"delete":{"ids":["2"]}}"#));
+    }
+
+    #[tokio::test]
+    async fn test_sync_retag() {
+        let (base, requests) = serve(|request| {
+            let body = if request.starts_with("GET") {
+                r#"{"data":[{"id":"1","value":"cat","tag":"old"}]}"#
+            } else if request.contains("\"add\"") {
+                r#"{"data":[{"id":"2","value":"cat","tag":"new"}],"meta":{"summary":{"created":1}}}"#
+            } else {
+                r#"{"meta":{"summary":{"deleted":1,"not_deleted":0}}}"#
+            };
+            json_response("200 OK", &[], body)
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/search/stream/rules", base);
+        let result = client
+            .stream_rules()
+            .with_url(&url)
+            .sync_rules(&[Rule::new("cat", Some("new"))], false)
+            .await
+            .unwrap();
+        assert_eq!(Some("2".to_owned()), result.added[0].id);
+        assert_eq!(Some("1".to_owned()), result.deleted[0].id);
+        let requests = requests.lock().unwrap();
+        assert_eq!(3, requests.len());
+        assert!(requests[1].contains(r#"{"delete":{"ids":["1"]}}"#));
+        assert!(requests[2].contains(r#"{"add":[{"tag":"new","value":"cat"}]}"#));
+    }
+
+    #[tokio::test]
+    async fn test_sync_dry_run() {
+        let (base, requests) = serve(|request| {
+            let body = if request.starts_with("GET") {
+                r#"{"data":[{"id":"1","value":"cat"},{"id":"2","value":"dog"}]}"#
+            } else if request.contains("\"add\"") {
+                r#"{"errors":[{"value":"(","title":"Invalid Rule","details":["Unmatched parenthesis."]}],"meta":{"summary":{"valid":1,"invalid":1}}}"#
+            } else {
+                r#"{"errors":[{"id":"2","title":"Not Found"}],"meta":{"summary":{"deleted":1,"not_deleted":1}}}"#
+            };
+            json_response("200 OK", &[], body)
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/search/stream/rules", base);
+        let desired = vec![Rule::new("bird", None), Rule::new("(", None)];
+        let result = client
+            .stream_rules()
+            .with_url(&url)
+            .sync_rules(&desired, true)
+            .await
+            .unwrap();
+        assert_eq!(vec![Rule::new("bird", None)], result.added);
+        assert_eq!(
+            vec![Some("1")],
+            result
+                .deleted
+                .iter()
+                .map(|it| it.id.as_deref())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, result.errors.len());
+        let requests = requests.lock().unwrap();
+        assert_eq!(3, requests.len());
+        assert!(requests[1].starts_with("POST /2/tweets/search/stream/rules?dry_run=true "));
+        assert!(requests[2].starts_with("POST /2/tweets/search/stream/rules?dry_run=true "));
+        assert!(requests[2].contains(r#"{"delete":{"ids":["1","2"]}}"#));
+    }
+}
@@ -0,0 +1,314 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+
+use crate::error::Error;
+use crate::oauth1::{Client, Provider};
+use crate::sign::{nonce, now};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Clone, Debug)]
+pub struct PendingLogin {
+    pub oauth_token: String,
+    pub oauth_token_secret: String,
+    pub expires_at: i64,
+}
+
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn insert(&self, state: &str, pending: PendingLogin) -> Result<(), Error>;
+
+    // Must remove the entry so a state value can only be used once.
+    async fn remove(&self, state: &str) -> Result<Option<PendingLogin>, Error>;
+}
+
+#[derive(Default)]
+pub struct MemorySessionStore {
+    entries: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn insert(&self, state: &str, pending: PendingLogin) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+        let current = now();
+        entries.retain(|_, it| it.expires_at > current);
+        entries.insert(state.to_owned(), pending);
+        Ok(())
+    }
+
+    async fn remove(&self, state: &str) -> Result<Option<PendingLogin>, Error> {
+        Ok(self.entries.lock().unwrap().remove(state))
+    }
+}
+
+pub struct LoginStart {
+    // Set this as a cookie and pass it back to SignIn::complete on callback.
+    pub state: String,
+    pub authorize_url: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct AccessToken {
+    pub oauth_token: String,
+    pub oauth_token_secret: String,
+    pub user_id: String,
+    pub screen_name: String,
+}
+
+pub struct SignIn<S: SessionStore> {
+    client: Client,
+    store: S,
+    ttl: Duration,
+}
+
+impl<S: SessionStore> SignIn<S> {
+    pub fn new(
+        consumer_key: &str,
+        consumer_secret: &str,
+        store: S,
+        timeout_sec: Option<Duration>,
+    ) -> Self {
+        let provider = Provider::new(
+            "https://api.twitter.com/oauth/request_token",
+            "https://api.twitter.com/oauth/authenticate",
+            "https://api.twitter.com/oauth/access_token",
+        );
+        Self::from_client(
+            Client::new(provider, consumer_key, consumer_secret, timeout_sec),
+            store,
+        )
+    }
+
+    pub fn from_client(client: Client, store: S) -> Self {
+        Self {
+            client,
+            store,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub async fn begin(&self, oauth_callback: &str) -> Result<LoginStart, Error> {
+        let result = check_oauth_body(self.client.request_token(oauth_callback, &vec![]).await?)?;
+        let oauth_token = result.get("oauth_token").cloned().unwrap_or_default();
+        let state = nonce();
+        self.store
+            .insert(
+                &state,
+                PendingLogin {
+                    oauth_token: oauth_token.clone(),
+                    oauth_token_secret: result
+                        .get("oauth_token_secret")
+                        .cloned()
+                        .unwrap_or_default(),
+                    expires_at: now() + self.ttl.as_secs() as i64,
+                },
+            )
+            .await?;
+        Ok(LoginStart {
+            state,
+            authorize_url: self.client.authorize_url(&oauth_token),
+        })
+    }
+
+    pub async fn complete(
+        &self,
+        state: &str,
+        oauth_token: &str,
+        oauth_verifier: &str,
+    ) -> Result<AccessToken, Error> {
+        let pending = self.store.remove(state).await?.ok_or(Error::InvalidState)?;
+        if pending.expires_at <= now() {
+            return Err(Error::LoginExpired);
+        }
+        if !bool::from(pending.oauth_token.as_bytes().ct_eq(oauth_token.as_bytes())) {
+            return Err(Error::TokenMismatch);
+        }
+        let result = check_oauth_body(
+            self.client
+                .access_token(
+                    &pending.oauth_token,
+                    &pending.oauth_token_secret,
+                    oauth_verifier,
+                )
+                .await?,
+        )?;
+        let get = |key: &str| result.get(key).cloned().unwrap_or_default();
+        Ok(AccessToken {
+            oauth_token: get("oauth_token"),
+            oauth_token_secret: get("oauth_token_secret"),
+            user_id: get("user_id"),
+            screen_name: get("screen_name"),
+        })
+    }
+}
+
+fn check_oauth_body(result: HashMap<String, String>) -> Result<HashMap<String, String>, Error> {
+    if result.contains_key("oauth_token") {
+        return Ok(result);
+    }
+    Err(Error::Status {
+        status_code: result
+            .get("twapi_request_status_code")
+            .and_then(|it| it.parse().ok())
+            .unwrap_or(0),
+        body: result
+            .get("twapi_request_body")
+            .cloned()
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{form_response, serve};
+    use std::sync::Arc;
+
+    async fn signin() -> (SignIn<MemorySessionStore>, Arc<Mutex<Vec<String>>>) {
+        let (base, requests) = serve(|request| {
+            if request.contains("/oauth/request_token") {
+                form_response("oauth_token=rt&oauth_token_secret=rs&oauth_callback_confirmed=true")
+            } else {
+                form_response("oauth_token=at&oauth_token_secret=as&user_id=1&screen_name=name")
+            }
+        })
+        .await;
+        let provider = Provider::new(
+            &format!("{}/oauth/request_token", base),
+            &format!("{}/oauth/authenticate", base),
+            &format!("{}/oauth/access_token", base),
+        );
+        let client = Client::new(provider, "key", "secret", None);
+        (
+            SignIn::from_client(client, MemorySessionStore::new()),
+            requests,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_memory_session_store_expires() {
+        let store = MemorySessionStore::new();
+        let expired = PendingLogin {
+            oauth_token: "a".to_owned(),
+            oauth_token_secret: "b".to_owned(),
+            expires_at: now() - 1,
+        };
+        store.insert("old", expired.clone()).await.unwrap();
+        store
+            .insert(
+                "new",
+                PendingLogin {
+                    expires_at: now() + 60,
+                    ..expired
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(1, store.len());
+        assert!(store.remove("new").await.unwrap().is_some());
+        assert!(store.remove("new").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_complete_rejects_unknown_state() {
+        let signin = SignIn::new("key", "secret", MemorySessionStore::new(), None);
+        match signin.complete("unknown", "token", "verifier").await {
+            Err(Error::InvalidState) => {}
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_begin_and_complete() {
+        let (signin, requests) = signin().await;
+        let start = signin.begin("https://example.com/callback").await.unwrap();
+        assert!(start
+            .authorize_url
+            .ends_with("/oauth/authenticate?oauth_token=rt"));
+        assert_eq!(1, signin.store().len());
+        let token = signin
+            .complete(&start.state, "rt", "verifier")
+            .await
+            .unwrap();
+        assert_eq!(
+            ("at", "as", "1", "name"),
+            (
+                token.oauth_token.as_str(),
+                token.oauth_token_secret.as_str(),
+                token.user_id.as_str(),
+                token.screen_name.as_str()
+            )
+        );
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("oauth_callback=\"https%3A%2F%2Fexample.com%2Fcallback\""));
+        assert!(requests[1].contains("oauth_token=\"rt\""));
+        assert!(requests[1].contains("oauth_verifier=\"verifier\""));
+    }
+
+    #[tokio::test]
+    async fn test_state_is_single_use() {
+        let (signin, requests) = signin().await;
+        let start = signin.begin("oob").await.unwrap();
+        signin
+            .complete(&start.state, "rt", "verifier")
+            .await
+            .unwrap();
+        match signin.complete(&start.state, "rt", "verifier").await {
+            Err(Error::InvalidState) => {}
+            other => panic!("{:?}", other.err()),
+        }
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_complete_rejects_token_mismatch() {
+        let (signin, requests) = signin().await;
+        let start = signin.begin("oob").await.unwrap();
+        match signin.complete(&start.state, "other", "verifier").await {
+            Err(Error::TokenMismatch) => {}
+            other => panic!("{:?}", other.err()),
+        }
+        // The state is consumed by the failed attempt too.
+        assert!(signin.store().is_empty());
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_complete_rejects_expired_login() {
+        let (signin, requests) = signin().await;
+        let signin = signin.with_ttl(Duration::from_secs(0));
+        let start = signin.begin("oob").await.unwrap();
+        match signin.complete(&start.state, "rt", "verifier").await {
+            Err(Error::LoginExpired) => {}
+            other => panic!("{:?}", other.err()),
+        }
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,259 @@
+use reqwest::Response;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::timeline::id_from_time;
+use crate::webhook::{Webhook, SIGNATURE_HEADER};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventKind {
+    TweetCreate,
+    Favorite,
+    Follow,
+    Unfollow,
+    Block,
+    Unblock,
+    Mute,
+    Unmute,
+    DirectMessage,
+    DirectMessageIndicateTyping,
+    TweetDelete,
+    UserEvent,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 12] = [
+        Self::TweetCreate,
+        Self::Favorite,
+        Self::Follow,
+        Self::Unfollow,
+        Self::Block,
+        Self::Unblock,
+        Self::Mute,
+        Self::Unmute,
+        Self::DirectMessage,
+        Self::DirectMessageIndicateTyping,
+        Self::TweetDelete,
+        Self::UserEvent,
+    ];
+}
+
+// Builds Account Activity payloads for a subscribed user and delivers them signed
+// with the consumer secret, so webhook handlers can be exercised offline.
+pub struct Simulator {
+    webhook: Webhook,
+    for_user_id: String,
+    other_user_id: String,
+    timeout_sec: Option<Duration>,
+}
+
+impl Simulator {
+    pub fn new(consumer_secret: &str, for_user_id: &str, timeout_sec: Option<Duration>) -> Self {
+        Self {
+            webhook: Webhook::new(consumer_secret),
+            for_user_id: for_user_id.to_owned(),
+            other_user_id: String::from("2244994945"),
+            timeout_sec,
+        }
+    }
+
+    // The user following, liking or messaging the subscribed user.
+    pub fn with_other_user_id(mut self, other_user_id: &str) -> Self {
+        self.other_user_id = other_user_id.to_owned();
+        self
+    }
+
+    pub fn payload(&self, kind: EventKind) -> Value {
+        let now = SystemTime::now();
+        let timestamp_ms = now
+            .duration_since(UNIX_EPOCH)
+            .map(|it| it.as_millis())
+            .unwrap_or_default()
+            .to_string();
+        let id = id_from_time(now).to_string();
+        let me = user(&self.for_user_id, "subscribed_user");
+        let other = user(&self.other_user_id, "other_user");
+        let tweet = |author: &Value| {
+            json!({
+                "created_at": created_at(now),
+                "id": id.parse::<u64>().unwrap_or_default(),
+                "id_str": id,
+                "text": "Hello from the webhook simulator",
+                "source": "<a href=\"https://example.com\" rel=\"nofollow\">simulator</a>",
+                "truncated": false,
+                "user": author,
+                "entities": {"hashtags": [], "urls": [], "user_mentions": [], "symbols": []},
+                "favorite_count": 0,
+                "retweet_count": 0,
+                "lang": "en",
+                "timestamp_ms": timestamp_ms,
+            })
+        };
+        let action = |action: &str, source: &Value, target: &Value| {
+            json!({
+                "type": action,
+                "created_timestamp": timestamp_ms,
+                "source": source,
+                "target": target,
+            })
+        };
+        let (key, event) = match kind {
+            EventKind::TweetCreate => ("tweet_create_events", json!([tweet(&other)])),
+            EventKind::Favorite => (
+                "favorite_events",
+                json!([{
+                    "id": format!("{}_{}", self.other_user_id, id),
+                    "created_at": created_at(now),
+                    "timestamp_ms": timestamp_ms.parse::<u64>().unwrap_or_default(),
+                    "favorited_status": tweet(&me),
+                    "user": other,
+                }]),
+            ),
+            EventKind::Follow => ("follow_events", json!([action("follow", &other, &me)])),
+            EventKind::Unfollow => ("follow_events", json!([action("unfollow", &other, &me)])),
+            EventKind::Block => ("block_events", json!([action("block", &me, &other)])),
+            EventKind::Unblock => ("block_events", json!([action("unblock", &me, &other)])),
+            EventKind::Mute => ("mute_events", json!([action("mute", &me, &other)])),
+            EventKind::Unmute => ("mute_events", json!([action("unmute", &me, &other)])),
+            EventKind::DirectMessage => (
+                "direct_message_events",
+                json!([{
+                    "type": "message_create",
+                    "id": id,
+                    "created_timestamp": timestamp_ms,
+                    "message_create": {
+                        "target": {"recipient_id": self.for_user_id},
+                        "sender_id": self.other_user_id,
+                        "message_data": {
+                            "text": "Hello from the webhook simulator",
+                            "entities": {"hashtags": [], "symbols": [], "user_mentions": [], "urls": []},
+                        },
+                    },
+                }]),
+            ),
+            EventKind::DirectMessageIndicateTyping => (
+                "direct_message_indicate_typing_events",
+                json!([{
+                    "created_timestamp": timestamp_ms,
+                    "sender_id": self.other_user_id,
+                    "target": {"recipient_id": self.for_user_id},
+                }]),
+            ),
+            EventKind::TweetDelete => (
+                "tweet_delete_events",
+                json!([{
+                    "status": {"id": id, "user_id": self.for_user_id},
+                    "timestamp_ms": timestamp_ms,
+                }]),
+            ),
+            EventKind::UserEvent => (
+                "user_event",
+                json!({
+                    "revoke": {
+                        "date_time": created_at(now),
+                        "target": {"app_id": "13090192"},
+                        "source": {"user_id": self.for_user_id},
+                    },
+                }),
+            ),
+        };
+        let mut payload = json!({ "for_user_id": self.for_user_id });
+        payload[key] = event;
+        if matches!(
+            kind,
+            EventKind::DirectMessage | EventKind::DirectMessageIndicateTyping
+        ) {
+            payload["users"] = json!({
+                self.for_user_id.clone(): me,
+                self.other_user_id.clone(): other,
+            });
+        }
+        payload
+    }
+
+    // POSTs the payload with x-twitter-webhooks-signature computed over the exact body.
+    pub async fn send(&self, url: &str, payload: &Value) -> Result<Response, Error> {
+        let body = payload.to_string();
+        let signature = self.webhook.signature(body.as_bytes());
+        Ok(crate::build_client(self.timeout_sec)
+            .post(url)
+            .header("Content-Type", "application/json; charset=utf-8")
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await?)
+    }
+
+    pub async fn simulate(&self, url: &str, kind: EventKind) -> Result<Response, Error> {
+        self.send(url, &self.payload(kind)).await
+    }
+}
+
+fn user(id: &str, screen_name: &str) -> Value {
+    json!({
+        "id": id.parse::<u64>().unwrap_or_default(),
+        "id_str": id,
+        "name": screen_name,
+        "screen_name": screen_name,
+        "protected": false,
+        "verified": false,
+        "followers_count": 10,
+        "friends_count": 10,
+        "statuses_count": 100,
+        "created_at": "Thu Apr 06 15:24:15 +0000 2017",
+        "profile_image_url_https": "https://abs.twimg.com/sticky/default_profile_images/default_profile_normal.png",
+    })
+}
+
+// "Wed Oct 10 20:19:24 +0000 2018"
+fn created_at(time: SystemTime) -> String {
+    // "Wed, 10 Oct 2018 20:19:24 GMT"
+    let date = httpdate::fmt_http_date(time);
+    let parts: Vec<&str> = date.split(' ').collect();
+    format!(
+        "{} {} {} {} +0000 {}",
+        parts[0].trim_end_matches(','),
+        parts[2],
+        parts[1],
+        parts[4],
+        parts[3]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulator::*;
+    use crate::test_server::{json_response, serve};
+    use crate::webhook::ActivityEvent;
+
+    #[test]
+    fn test_created_at() {
+        let time = UNIX_EPOCH + Duration::from_secs(1539202764);
+        assert_eq!("Wed Oct 10 20:19:24 +0000 2018", created_at(time));
+    }
+
+    #[tokio::test]
+    async fn test_simulate() {
+        let (base, requests) = serve(|_| json_response("200 OK", &[], "{}")).await;
+        let simulator = Simulator::new("secret", "1", None);
+        let url = format!("{}/webhook", base);
+        for kind in EventKind::ALL.iter() {
+            simulator.simulate(&url, *kind).await.unwrap();
+        }
+        let webhook = Webhook::new("secret");
+        let requests = requests.lock().unwrap();
+        assert_eq!(EventKind::ALL.len(), requests.len());
+        for request in requests.iter() {
+            let (head, body) = request.split_once("\r\n\r\n").unwrap();
+            let signature = head
+                .lines()
+                .find_map(|line| line.strip_prefix("x-twitter-webhooks-signature: "))
+                .unwrap();
+            let payload = webhook.parse(body.as_bytes(), signature).unwrap();
+            assert_eq!("1", payload.for_user_id);
+            assert_eq!(1, payload.events.len());
+            assert!(!matches!(payload.events[0], ActivityEvent::Unknown { .. }));
+        }
+    }
+}
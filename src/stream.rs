@@ -0,0 +1,582 @@
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use reqwest::Response;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+
+pub const FILTERED_STREAM_URL: &str = "https://api.twitter.com/2/tweets/search/stream";
+pub const SAMPLE_STREAM_URL: &str = "https://api.twitter.com/2/tweets/sample/stream";
+pub const SAMPLE10_STREAM_URL: &str = "https://api.twitter.com/2/tweets/sample10/stream";
+pub const FIREHOSE_STREAM_URL: &str = "https://api.twitter.com/2/tweets/firehose/stream";
+pub const LEGACY_FILTER_STREAM_URL: &str = "https://stream.twitter.com/1.1/statuses/filter.json";
+pub const LEGACY_SAMPLE_STREAM_URL: &str = "https://stream.twitter.com/1.1/statuses/sample.json";
+
+pub const SAMPLE10_PARTITIONS: u32 = 2;
+pub const FIREHOSE_PARTITIONS: u32 = 20;
+
+// Twitter keeps five minutes of data for recovery.
+pub const MAX_BACKFILL_MINUTES: u32 = 5;
+
+const STALL_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug)]
+pub enum StreamEvent<T> {
+    Connected,
+    Message(T),
+    KeepAlive,
+    Disconnected { reason: String },
+    Reconnecting { attempt: u32, delay: Duration },
+}
+
+// v1.1 streaming messages. After Disconnect the connection is closed by Twitter
+// and consume reconnects with the usual backoff.
+#[derive(Debug)]
+pub enum LegacyMessage {
+    Tweet(Value),
+    Delete {
+        id: u64,
+        user_id: u64,
+    },
+    // Number of undelivered tweets since the connection was opened.
+    Limit {
+        track: u64,
+    },
+    StallWarning {
+        code: String,
+        message: String,
+        percent_full: u64,
+    },
+    Disconnect {
+        code: u64,
+        stream_name: String,
+        reason: String,
+    },
+    Other(Value),
+}
+
+impl LegacyMessage {
+    pub fn from_json(json: Value) -> Self {
+        let u64_of = |json: &Value, key: &str| {
+            json[format!("{}_str", key).as_str()]
+                .as_str()
+                .and_then(|it| it.parse().ok())
+                .or_else(|| json[key].as_u64())
+                .unwrap_or_default()
+        };
+        let string_of = |json: &Value, key: &str| json[key].as_str().unwrap_or_default().to_owned();
+        if let Some(status) = json["delete"].get("status") {
+            Self::Delete {
+                id: u64_of(status, "id"),
+                user_id: u64_of(status, "user_id"),
+            }
+        } else if let Some(limit) = json.get("limit") {
+            Self::Limit {
+                track: u64_of(limit, "track"),
+            }
+        } else if let Some(warning) = json.get("warning") {
+            Self::StallWarning {
+                code: string_of(warning, "code"),
+                message: string_of(warning, "message"),
+                percent_full: u64_of(warning, "percent_full"),
+            }
+        } else if let Some(disconnect) = json.get("disconnect") {
+            Self::Disconnect {
+                code: u64_of(disconnect, "code"),
+                stream_name: string_of(disconnect, "stream_name"),
+                reason: string_of(disconnect, "reason"),
+            }
+        } else if json.get("id_str").is_some() && json.get("user").is_some() {
+            Self::Tweet(json)
+        } else {
+            Self::Other(json)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Framing {
+    // One JSON object per \r\n terminated line.
+    Lines,
+    // v1.1 delimited=length, a byte count line precedes each message.
+    DelimitedLength,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Failure {
+    Network,
+    Http,
+    RateLimited,
+}
+
+// Backoff recommended by Twitter: linear from 250ms to 16s for network errors,
+// exponential from 5s to 320s for HTTP errors, exponential from 1 minute for 429.
+fn backoff(failure: Failure, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    match failure {
+        Failure::Network => Duration::from_millis((250 * attempt as u64).min(16_000)),
+        Failure::Http => Duration::from_secs((5u64 << exponent).min(320)),
+        Failure::RateLimited => Duration::from_secs((60u64 << exponent).min(960)),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StreamOptions {
+    pub stall_timeout: Duration,
+    // None reconnects forever.
+    pub max_attempts: Option<u32>,
+    // Sent as backfill_minutes, capped at MAX_BACKFILL_MINUTES. Needs an Academic/Enterprise project.
+    pub backfill_minutes: Option<u32>,
+    // Size backfill_minutes to the time since the last received data when reconnecting.
+    pub backfill_on_reconnect: bool,
+    // Last data received before a restart, the first connection then counts as a reconnect.
+    pub resume_from: Option<SystemTime>,
+}
+
+impl StreamOptions {
+    fn backfill(&self, last_received: Option<SystemTime>) -> Option<u32> {
+        let outage = last_received
+            .filter(|_| self.backfill_on_reconnect)
+            .map(|it| SystemTime::now().duration_since(it).unwrap_or_default());
+        match outage {
+            Some(outage) => Some(outage.as_secs().div_ceil(60) as u32),
+            None => self.backfill_minutes,
+        }
+        .filter(|it| *it > 0)
+        .map(|it| it.min(MAX_BACKFILL_MINUTES))
+    }
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            stall_timeout: STALL_TIMEOUT,
+            max_attempts: None,
+            backfill_minutes: None,
+            backfill_on_reconnect: false,
+            resume_from: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Frame {
+    KeepAlive,
+    Message(Vec<u8>),
+}
+
+pub(crate) struct Decoder {
+    framing: Framing,
+    buffer: Vec<u8>,
+    length: Option<usize>,
+}
+
+impl Decoder {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            buffer: vec![],
+            length: None,
+        }
+    }
+
+    // Keeps partial chunks until a complete frame arrives.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Frame> {
+        self.buffer.extend_from_slice(chunk);
+        let mut frames = vec![];
+        loop {
+            if let Some(length) = self.length {
+                if self.buffer.len() < length {
+                    break;
+                }
+                let message: Vec<u8> = self.buffer.drain(..length).collect();
+                self.length = None;
+                let message = trim(&message);
+                if !message.is_empty() {
+                    frames.push(Frame::Message(message.to_vec()));
+                }
+                continue;
+            }
+            let index = match self.buffer.iter().position(|it| *it == b'\n') {
+                Some(index) => index,
+                None => break,
+            };
+            let line: Vec<u8> = self.buffer.drain(..=index).collect();
+            let line = trim(&line);
+            if line.is_empty() {
+                frames.push(Frame::KeepAlive);
+                continue;
+            }
+            match self.framing {
+                Framing::Lines => frames.push(Frame::Message(line.to_vec())),
+                Framing::DelimitedLength => {
+                    match std::str::from_utf8(line)
+                        .ok()
+                        .and_then(|it| it.parse().ok())
+                    {
+                        Some(length) => self.length = Some(length),
+                        None => frames.push(Frame::Message(line.to_vec())),
+                    }
+                }
+            }
+        }
+        frames
+    }
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|it| !it.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|it| !it.is_ascii_whitespace())
+        .map(|it| it + 1)
+        .unwrap_or(start);
+    &bytes[start..end]
+}
+
+// Called with the backfill_minutes to request on this connection.
+pub(crate) type Connector<'a> =
+    Box<dyn Fn(Option<u32>) -> BoxFuture<'a, Result<Response, Error>> + Send + Sync + 'a>;
+
+pub(crate) type Parser<T> = Box<dyn Fn(Value) -> Result<T, Error> + Send + Sync>;
+
+struct State<'a, T> {
+    connector: Connector<'a>,
+    parser: Parser<T>,
+    options: StreamOptions,
+    decoder: Decoder,
+    body: Option<BoxStream<'a, Result<Bytes, reqwest::Error>>>,
+    attempt: u32,
+    delay: Option<Duration>,
+    last_received: Option<SystemTime>,
+    pending: VecDeque<Result<StreamEvent<T>, Error>>,
+    done: bool,
+}
+
+impl<'a, T> State<'a, T> {
+    fn schedule(&mut self, failure: Failure) {
+        self.body = None;
+        self.decoder = Decoder::new(self.decoder.framing);
+        self.attempt += 1;
+        if self
+            .options
+            .max_attempts
+            .is_some_and(|it| self.attempt > it)
+        {
+            self.done = true;
+            return;
+        }
+        let delay = backoff(failure, self.attempt);
+        self.delay = Some(delay);
+        self.pending.push_back(Ok(StreamEvent::Reconnecting {
+            attempt: self.attempt,
+            delay,
+        }));
+    }
+
+    fn disconnect(&mut self, reason: String, failure: Failure) {
+        self.pending
+            .push_back(Ok(StreamEvent::Disconnected { reason }));
+        self.schedule(failure);
+    }
+
+    async fn connect(&mut self) {
+        if let Some(delay) = self.delay.take() {
+            tokio::time::sleep(delay).await;
+        }
+        let backfill = self.options.backfill(self.last_received);
+        match (self.connector)(backfill).await {
+            Ok(response) if response.status().is_success() => {
+                self.body = Some(response.bytes_stream().boxed());
+                self.pending.push_back(Ok(StreamEvent::Connected));
+            }
+            Ok(response) => {
+                let status_code = response.status().as_u16();
+                let failure = match status_code {
+                    420 | 429 => Failure::RateLimited,
+                    500..=599 => Failure::Http,
+                    _ => {
+                        // Authentication and request errors do not heal by reconnecting.
+                        self.done = true;
+                        self.pending.push_back(Err(Error::Status {
+                            status_code,
+                            body: response.text().await.unwrap_or_default(),
+                        }));
+                        return;
+                    }
+                };
+                let reason = format!("HTTP {}", status_code);
+                self.disconnect(reason, failure);
+            }
+            Err(Error::Reqwest(err)) => self.disconnect(err.to_string(), Failure::Network),
+            Err(err) => {
+                self.done = true;
+                self.pending.push_back(Err(err));
+            }
+        }
+    }
+
+    async fn read(&mut self) {
+        let body = match self.body.as_mut() {
+            Some(body) => body,
+            None => return,
+        };
+        match tokio::time::timeout(self.options.stall_timeout, body.next()).await {
+            Err(_) => self.disconnect(String::from("stall"), Failure::Network),
+            Ok(None) => self.disconnect(String::from("closed"), Failure::Network),
+            Ok(Some(Err(err))) => self.disconnect(err.to_string(), Failure::Network),
+            Ok(Some(Ok(chunk))) => {
+                let frames = self.decoder.push(&chunk);
+                if !frames.is_empty() {
+                    self.last_received = Some(SystemTime::now());
+                }
+                for frame in frames {
+                    self.attempt = 0;
+                    let event = match frame {
+                        Frame::KeepAlive => Ok(StreamEvent::KeepAlive),
+                        Frame::Message(message) => serde_json::from_slice(&message)
+                            .map_err(Error::Json)
+                            .and_then(|json| (self.parser)(json))
+                            .map(StreamEvent::Message),
+                    };
+                    self.pending.push_back(event);
+                }
+            }
+        }
+    }
+}
+
+// Yields events until a non recoverable error or max_attempts, reconnecting in between.
+// Errors that are not followed by the end of the stream (bad JSON) can be skipped.
+pub(crate) fn consume<'a, T: Send + 'a>(
+    connector: Connector<'a>,
+    framing: Framing,
+    parser: Parser<T>,
+    options: StreamOptions,
+) -> impl Stream<Item = Result<StreamEvent<T>, Error>> + 'a {
+    let state = State {
+        connector,
+        parser,
+        last_received: options.resume_from,
+        options,
+        decoder: Decoder::new(framing),
+        body: None,
+        attempt: 0,
+        delay: None,
+        pending: VecDeque::new(),
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((event, state));
+            }
+            if state.done {
+                return None;
+            }
+            if state.body.is_some() {
+                state.read().await;
+            } else {
+                state.connect().await;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve;
+
+    #[test]
+    fn test_decoder_lines() {
+        let mut decoder = Decoder::new(Framing::Lines);
+        assert!(decoder.push(b"{\"data\":").is_empty());
+        assert_eq!(
+            vec![Frame::Message(b"{\"data\":1}".to_vec()), Frame::KeepAlive],
+            decoder.push(b"1}\r\n\r\n")
+        );
+    }
+
+    #[test]
+    fn test_decoder_delimited_length() {
+        let mut decoder = Decoder::new(Framing::DelimitedLength);
+        assert_eq!(vec![Frame::KeepAlive], decoder.push(b"\r\n11\r\n{\"a\":"));
+        assert_eq!(
+            vec![Frame::Message(b"{\"a\":\"\n\"}".to_vec())],
+            decoder.push(b"\"\n\"}\r\n")
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(Duration::from_millis(250), backoff(Failure::Network, 1));
+        assert_eq!(Duration::from_secs(16), backoff(Failure::Network, 100));
+        assert_eq!(Duration::from_secs(10), backoff(Failure::Http, 2));
+        assert_eq!(Duration::from_secs(320), backoff(Failure::Http, 10));
+        assert_eq!(Duration::from_secs(120), backoff(Failure::RateLimited, 2));
+    }
+
+    #[test]
+    fn test_backfill() {
+        let mut options = StreamOptions {
+            backfill_minutes: Some(2),
+            ..Default::default()
+        };
+        let last_received = SystemTime::now() - Duration::from_secs(90);
+        assert_eq!(Some(2), options.backfill(Some(last_received)));
+        options.backfill_on_reconnect = true;
+        assert_eq!(Some(2), options.backfill(Some(last_received)));
+        assert_eq!(Some(2), options.backfill(None));
+        let last_received = SystemTime::now() - Duration::from_secs(3600);
+        assert_eq!(Some(5), options.backfill(Some(last_received)));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_stream() {
+        let (base, _) = serve(|_| {
+            String::from("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\n\r\n{\"data\":{\"id\":\"1\"}}\r\n\r\n")
+        })
+        .await;
+        let client = crate::v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/search/stream", base);
+        let options = StreamOptions {
+            max_attempts: Some(0),
+            ..Default::default()
+        };
+        let events: Vec<_> = client.stream(&url, &vec![], options).collect().await;
+        assert!(matches!(events[0], Ok(StreamEvent::Connected)));
+        match &events[1] {
+            Ok(StreamEvent::Message(json)) => assert_eq!("1", json["data"]["id"]),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(events[2], Ok(StreamEvent::KeepAlive)));
+        assert!(matches!(events[3], Ok(StreamEvent::Disconnected { .. })));
+        assert_eq!(4, events.len());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_filter_stream() {
+        let (base, requests) = serve(|_| {
+            let messages = [
+                r#"{"id_str":"10","text":"hello","user":{"id_str":"1"}}"#,
+                r#"{"delete":{"status":{"id":10,"id_str":"10","user_id":1,"user_id_str":"1"}}}"#,
+                r#"{"limit":{"track":42}}"#,
+                r#"{"warning":{"code":"FALLING_BEHIND","message":"behind","percent_full":60}}"#,
+                r#"{"disconnect":{"code":7,"stream_name":"name","reason":"admin logout"}}"#,
+            ];
+            let mut body = String::new();
+            for message in messages.iter() {
+                body.push_str(&format!("{}\r\n{}\r\n", message.len() + 2, message));
+            }
+            format!("HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n{}", body)
+        })
+        .await;
+        let client = crate::v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/statuses/filter.json", base);
+        let options = StreamOptions {
+            max_attempts: Some(0),
+            ..Default::default()
+        };
+        let events: Vec<_> = client
+            .stream(&url, &vec![("track", "twitter")], options)
+            .filter_map(|it| async move {
+                match it {
+                    Ok(StreamEvent::Message(message)) => Some(message),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        assert!(matches!(events[0], LegacyMessage::Tweet(_)));
+        assert!(matches!(
+            events[1],
+            LegacyMessage::Delete { id: 10, user_id: 1 }
+        ));
+        assert!(matches!(events[2], LegacyMessage::Limit { track: 42 }));
+        assert!(matches!(
+            events[3],
+            LegacyMessage::StallWarning {
+                percent_full: 60,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[4],
+            LegacyMessage::Disconnect { code: 7, .. }
+        ));
+        let request = &requests.lock().unwrap()[0];
+        assert!(request.starts_with("POST"));
+        assert!(request.contains("track=twitter&delimited=length&stall_warnings=true"));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sample_stream() {
+        let (base, requests) = serve(|_| {
+            let message = r#"{"id_str":"10","text":"hello","user":{"id_str":"1"}}"#;
+            format!(
+                "HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n{}\r\n{}\r\n",
+                message.len() + 2,
+                message
+            )
+        })
+        .await;
+        let client = crate::v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/statuses/sample.json", base);
+        let options = StreamOptions {
+            max_attempts: Some(0),
+            ..Default::default()
+        };
+        let events: Vec<_> = client
+            .get_stream(&url, &vec![("language", "en")], options)
+            .collect()
+            .await;
+        assert!(matches!(events[0], Ok(StreamEvent::Connected)));
+        assert!(matches!(
+            events[1],
+            Ok(StreamEvent::Message(LegacyMessage::Tweet(_)))
+        ));
+        let request = &requests.lock().unwrap()[0];
+        assert!(request.starts_with(
+            "GET /1.1/statuses/sample.json?language=en&delimited=length&stall_warnings=true "
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sample10_stream() {
+        let (base, requests) = serve(|request| {
+            let id = if request.contains("partition=1") { "1" } else { "2" };
+            format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\n\r\n{{\"data\":{{\"id\":\"{}\"}}}}\r\n", id)
+        })
+        .await;
+        let client = crate::v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/sample10/stream", base);
+        let options = StreamOptions {
+            max_attempts: Some(0),
+            backfill_minutes: Some(10),
+            ..Default::default()
+        };
+        let mut messages: Vec<(u32, String)> = client
+            .partitioned_stream(&url, &[1, 2], &[], options)
+            .filter_map(|(partition, event)| async move {
+                match event {
+                    Ok(StreamEvent::Message(json)) => {
+                        Some((partition, json["data"]["id"].as_str()?.to_owned()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        messages.sort();
+        assert_eq!(vec![(1, "1".to_owned()), (2, "2".to_owned())], messages);
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|it| it.contains("backfill_minutes=5")));
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// Minimal HTTP/1.1 server answering each request with `handler(request)`.
+pub(crate) async fn serve<F>(handler: F) -> (String, Arc<Mutex<Vec<String>>>)
+where
+    F: Fn(&str) -> String + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let captured = requests.clone();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let captured = captured.clone();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let request = read_request(&mut socket).await;
+                captured.lock().unwrap().push(request.clone());
+                let response = handler(&request);
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+    (base, requests)
+}
+
+pub(crate) fn json_response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (key, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    response
+}
+
+pub(crate) fn form_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: application/x-www-form-urlencoded\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut buffer = vec![];
+    let mut chunk = [0u8; 4096];
+    loop {
+        let size = socket.read(&mut chunk).await.unwrap_or(0);
+        if size == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..size]);
+        let text = String::from_utf8_lossy(&buffer).to_string();
+        if let Some(index) = text.find("\r\n\r\n") {
+            let content_length = text[..index]
+                .lines()
+                .find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    if key.eq_ignore_ascii_case("content-length") {
+                        value.trim().parse::<usize>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(0);
+            if buffer.len() >= index + 4 + content_length {
+                break;
+            }
+        }
+    }
+    String::from_utf8_lossy(&buffer).to_string()
+}
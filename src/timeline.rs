@@ -0,0 +1,347 @@
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::client::ApiClient;
+use crate::error::Error;
+use crate::pagination::get_waiting;
+
+// Snowflake ids carry milliseconds since this epoch in the bits above 22.
+const TWITTER_EPOCH_MS: u64 = 1288834974657;
+
+pub fn id_from_time(time: SystemTime) -> u64 {
+    let ms = time
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_millis() as u64)
+        .unwrap_or(0);
+    ms.saturating_sub(TWITTER_EPOCH_MS) << 22
+}
+
+pub fn time_from_id(id: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis((id >> 22) + TWITTER_EPOCH_MS)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Floor {
+    None,
+    // Exclusive, like since_id.
+    Id(u64),
+    Time(SystemTime),
+}
+
+impl Floor {
+    fn id(&self) -> Option<u64> {
+        match self {
+            Self::None => None,
+            Self::Id(id) => Some(*id),
+            Self::Time(time) => Some(id_from_time(*time)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Poll {
+    // Newest first.
+    pub items: Vec<Value>,
+    // Persist this and pass it to the next poll.
+    pub since_id: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Style {
+    // v1.1 max_id is inclusive, so the next page asks for the oldest id minus one.
+    MaxId,
+    // v2 until_id is exclusive.
+    UntilId,
+}
+
+pub struct TimelineWalker<'a, C: ApiClient> {
+    client: &'a C,
+    url: String,
+    query_options: Vec<(String, String)>,
+    style: Style,
+    max_pages: Option<usize>,
+}
+
+struct State<'a, C: ApiClient> {
+    walker: TimelineWalker<'a, C>,
+    floor: Option<u64>,
+    upper: Option<u64>,
+    seen: HashSet<u64>,
+    pages: usize,
+    done: bool,
+}
+
+impl<'a, C: ApiClient> TimelineWalker<'a, C> {
+    pub fn new(client: &'a C, url: &str, query_options: &[(&str, &str)]) -> Self {
+        Self {
+            client,
+            url: url.to_owned(),
+            query_options: query_options
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            style: Style::MaxId,
+            max_pages: None,
+        }
+    }
+
+    // v2::Client::timeline uses UntilId, set MaxId to walk a v1.1 endpoint with it.
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    // Returns the items of one page and the next upper bound, None at the end.
+    async fn fetch(
+        &self,
+        floor: Option<u64>,
+        upper: Option<u64>,
+    ) -> Result<(Vec<(u64, Value)>, Option<u64>), Error> {
+        let floor = floor.map(|it| it.to_string());
+        let upper = upper.map(|it| it.to_string());
+        let mut query_options: Vec<(&str, &str)> = self
+            .query_options
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        if let Some(floor) = &floor {
+            query_options.push(("since_id", floor));
+        }
+        if let Some(upper) = &upper {
+            match self.style {
+                Style::MaxId => query_options.push(("max_id", upper)),
+                Style::UntilId => query_options.push(("until_id", upper)),
+            }
+        }
+        let response = get_waiting(self.client, &self.url, &query_options, true).await?;
+        let json: Value = crate::check_status(response).await?.json().await?;
+        let items: Vec<(u64, Value)> = items_of(json)
+            .into_iter()
+            .filter_map(|item| id_of(&item).map(|id| (id, item)))
+            .collect();
+        let next = items
+            .iter()
+            .map(|(id, _)| *id)
+            .min()
+            .and_then(|oldest| match self.style {
+                Style::MaxId => oldest.checked_sub(1),
+                Style::UntilId => Some(oldest),
+            });
+        Ok((items, next))
+    }
+
+    // Walks from the newest item back to the floor, yielding each item once.
+    pub fn backward(self, floor: Floor) -> impl Stream<Item = Result<Value, Error>> + 'a {
+        let state = State {
+            floor: floor.id(),
+            walker: self,
+            upper: None,
+            seen: HashSet::new(),
+            pages: 0,
+            done: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            if state.done || state.walker.max_pages.is_some_and(|it| state.pages >= it) {
+                return None;
+            }
+            state.pages += 1;
+            match state.walker.fetch(state.floor, state.upper).await {
+                Ok((items, next)) => {
+                    let floor = state.floor.unwrap_or(0);
+                    let fetched = items.len();
+                    let mut result = vec![];
+                    for (id, item) in items {
+                        if id > floor && state.seen.insert(id) {
+                            result.push(Ok(item));
+                        }
+                    }
+                    // Stop when the page is empty or does not move the window.
+                    let moved = match (next, state.upper) {
+                        (Some(next), Some(upper)) => next < upper,
+                        (Some(_), None) => true,
+                        (None, _) => false,
+                    };
+                    state.done = fetched == 0 || !moved || next.is_some_and(|it| it <= floor);
+                    state.upper = next;
+                    Some((stream::iter(result), state))
+                }
+                Err(err) => {
+                    state.done = true;
+                    Some((stream::iter(vec![Err(err)]), state))
+                }
+            }
+        })
+        .flatten()
+    }
+
+    // Fetches everything newer than since_id. Without a checkpoint only the first page is read.
+    pub async fn poll(self, since_id: Option<u64>) -> Result<Poll, Error> {
+        let walker = match since_id {
+            Some(_) => self,
+            None => self.with_max_pages(1),
+        };
+        let floor = since_id.map(Floor::Id).unwrap_or(Floor::None);
+        let items: Vec<Value> = walker
+            .backward(floor)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        let newest = items.iter().filter_map(id_of).max();
+        Ok(Poll {
+            since_id: newest.or(since_id),
+            items,
+        })
+    }
+}
+
+fn items_of(json: Value) -> Vec<Value> {
+    match json {
+        Value::Array(items) => items,
+        mut json => ["data", "statuses"]
+            .iter()
+            .find_map(|key| match json[*key].take() {
+                Value::Array(items) => Some(items),
+                _ => None,
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn id_of(item: &Value) -> Option<u64> {
+    item["id_str"]
+        .as_str()
+        .or_else(|| item["id"].as_str())
+        .and_then(|it| it.parse().ok())
+        .or_else(|| item["id"].as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_server::{json_response, serve};
+    use crate::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_snowflake_time() {
+        let id = 1445078208190291973u64;
+        let time = timeline::time_from_id(id);
+        assert!(timeline::id_from_time(time) <= id);
+        assert!(timeline::id_from_time(time) > id - (1 << 22));
+    }
+
+    #[tokio::test]
+    async fn test_backward_max_id() {
+        let (base, requests) = serve(|request| {
+            let body = if request.contains("max_id=17") {
+                // Overlaps the previous page on id 18.
+                r#"[{"id_str":"18"},{"id_str":"15"},{"id_str":"12"}]"#
+            } else if request.contains("max_id=11") {
+                "[]"
+            } else {
+                r#"[{"id_str":"20"},{"id_str":"19"},{"id_str":"18"}]"#
+            };
+            json_response("200 OK", &[], body)
+        })
+        .await;
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/statuses/user_timeline.json", base);
+        let items: Vec<_> = client
+            .timeline(&url, &vec![("count", "3")])
+            .backward(timeline::Floor::Id(13))
+            .map(|it| it.unwrap()["id_str"].as_str().unwrap().to_owned())
+            .collect()
+            .await;
+        assert_eq!(vec!["20", "19", "18", "15"], items);
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("since_id=13"));
+        assert!(requests[1].contains("max_id=17"));
+    }
+
+    fn page(ids: &[u64]) -> String {
+        let data = ids
+            .iter()
+            .map(|id| format!(r#"{{"id":"{}"}}"#, id))
+            .collect::<Vec<_>>()
+            .join(",");
+        json_response("200 OK", &[], &format!(r#"{{"data":[{}]}}"#, data))
+    }
+
+    fn ids(items: &[serde_json::Value]) -> Vec<&str> {
+        items.iter().map(|it| it["id"].as_str().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_poll_until_id() {
+        let (base, requests) = serve(|request| {
+            if request.contains("until_id=12") {
+                // Overlaps the previous page on id 12.
+                page(&[12, 11])
+            } else if request.contains("until_id=11") {
+                page(&[])
+            } else {
+                page(&[14, 13, 12])
+            }
+        })
+        .await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/users/1/tweets", base);
+        let poll = client
+            .timeline(&url, &vec![("max_results", "3")])
+            .poll(Some(10))
+            .await
+            .unwrap();
+        assert_eq!(vec!["14", "13", "12", "11"], ids(&poll.items));
+        assert_eq!(Some(14), poll.since_id);
+        let requests = requests.lock().unwrap();
+        assert_eq!(3, requests.len());
+        assert!(requests[0].contains("max_results=3&since_id=10 "));
+        assert!(requests[1].contains("since_id=10&until_id=12 "));
+        assert!(requests[2].contains("since_id=10&until_id=11 "));
+    }
+
+    #[tokio::test]
+    async fn test_poll_without_checkpoint() {
+        let (base, requests) = serve(|_| page(&[14, 13])).await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/users/1/tweets", base);
+        let poll = client.timeline(&url, &vec![]).poll(None).await.unwrap();
+        assert_eq!(vec!["14", "13"], ids(&poll.items));
+        assert_eq!(Some(14), poll.since_id);
+        assert_eq!(1, requests.lock().unwrap().len());
+
+        let (base, _) = serve(|_| page(&[])).await;
+        let url = format!("{}/2/users/1/tweets", base);
+        let poll = client.timeline(&url, &vec![]).poll(Some(14)).await.unwrap();
+        assert!(poll.items.is_empty());
+        assert_eq!(Some(14), poll.since_id);
+    }
+
+    #[tokio::test]
+    async fn test_backward_time_floor() {
+        let now = std::time::SystemTime::now();
+        let floor = timeline::id_from_time(now);
+        let newer = floor + (1 << 22);
+        let older = floor - (1 << 22);
+        let (base, requests) = serve(move |_| page(&[newer, older])).await;
+        let client = v2::Client::new("token", None);
+        let url = format!("{}/2/tweets/search/recent", base);
+        let items: Vec<_> = client
+            .timeline(&url, &vec![])
+            .backward(timeline::Floor::Time(now))
+            .map(|it| it.unwrap())
+            .collect()
+            .await;
+        assert_eq!(vec![newer.to_string()], ids(&items));
+        let requests = requests.lock().unwrap();
+        assert_eq!(1, requests.len());
+        assert!(requests[0].contains(&format!("since_id={}", floor)));
+    }
+}
@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+
+// A resumed upload needs time left for the remaining APPENDs and FINALIZE.
+const RESUME_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadState {
+    pub media_id: String,
+    pub total_bytes: u64,
+    pub segment_size: usize,
+    // Identifies the source, see fingerprint.
+    pub fingerprint: String,
+    pub next_segment_index: u64,
+    pub finalized: bool,
+    pub expires_after_secs: u64,
+    pub initialized_at: SystemTime,
+}
+
+impl UploadState {
+    pub fn expires_at(&self) -> SystemTime {
+        self.initialized_at + Duration::from_secs(self.expires_after_secs)
+    }
+
+    pub fn can_resume(&self, total_bytes: u64, segment_size: usize, fingerprint: &str) -> bool {
+        self.total_bytes == total_bytes
+            && self.segment_size == segment_size
+            && self.fingerprint == fingerprint
+            && SystemTime::now() + RESUME_MARGIN < self.expires_at()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "media_id": self.media_id,
+            "total_bytes": self.total_bytes,
+            "segment_size": self.segment_size,
+            "fingerprint": self.fingerprint,
+            "next_segment_index": self.next_segment_index,
+            "finalized": self.finalized,
+            "expires_after_secs": self.expires_after_secs,
+            "initialized_at": self
+                .initialized_at
+                .duration_since(UNIX_EPOCH)
+                .map(|it| it.as_secs())
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn from_json(json: &Value) -> Option<Self> {
+        Some(Self {
+            media_id: json["media_id"].as_str()?.to_owned(),
+            total_bytes: json["total_bytes"].as_u64()?,
+            segment_size: json["segment_size"].as_u64()? as usize,
+            fingerprint: json["fingerprint"].as_str()?.to_owned(),
+            next_segment_index: json["next_segment_index"].as_u64()?,
+            finalized: json["finalized"].as_bool()?,
+            expires_after_secs: json["expires_after_secs"].as_u64()?,
+            initialized_at: UNIX_EPOCH + Duration::from_secs(json["initialized_at"].as_u64()?),
+        })
+    }
+}
+
+// SHA-256 of the upload key when the caller gives one, otherwise of the first segment.
+pub fn fingerprint(upload_key: Option<&str>, first_segment: &[u8]) -> String {
+    let digest = match upload_key {
+        Some(upload_key) => Sha256::digest(upload_key.as_bytes()),
+        None => Sha256::digest(first_segment),
+    };
+    base64::encode(digest)
+}
+
+#[async_trait]
+pub trait UploadStateStore: Send + Sync {
+    async fn load(&self) -> Result<Option<UploadState>, Error>;
+
+    async fn save(&self, state: UploadState) -> Result<(), Error>;
+
+    async fn clear(&self) -> Result<(), Error>;
+}
+
+#[derive(Default)]
+pub struct MemoryUploadStateStore {
+    state: Mutex<Option<UploadState>>,
+}
+
+impl MemoryUploadStateStore {
+    pub fn new(state: Option<UploadState>) -> Self {
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+}
+
+#[async_trait]
+impl UploadStateStore for MemoryUploadStateStore {
+    async fn load(&self) -> Result<Option<UploadState>, Error> {
+        Ok(self.state.lock().unwrap().clone())
+    }
+
+    async fn save(&self, state: UploadState) -> Result<(), Error> {
+        *self.state.lock().unwrap() = Some(state);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), Error> {
+        *self.state.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+// One JSON file per upload, replaced through a temporary file so a crash never
+// leaves a partial write.
+pub struct FileUploadStateStore {
+    path: PathBuf,
+}
+
+impl FileUploadStateStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl UploadStateStore for FileUploadStateStore {
+    async fn load(&self) -> Result<Option<UploadState>, Error> {
+        let text = match tokio::fs::read_to_string(&self.path).await {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let json: Value =
+            serde_json::from_str(&text).map_err(|err| Error::UploadStateStore(err.to_string()))?;
+        UploadState::from_json(&json)
+            .map(Some)
+            .ok_or_else(|| Error::UploadStateStore(format!("invalid state {}", text)))
+    }
+
+    async fn save(&self, state: UploadState) -> Result<(), Error> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        tokio::fs::write(&temporary, state.to_json().to_string()).await?;
+        tokio::fs::rename(&temporary, &self.path).await?;
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), Error> {
+        match tokio::fs::remove_file(&self.path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::upload_state::*;
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let path = std::env::temp_dir().join(format!("upload_state_{}.json", std::process::id()));
+        let store = FileUploadStateStore::new(&path);
+        assert_eq!(None, store.load().await.unwrap());
+        let state = UploadState {
+            media_id: String::from("1"),
+            total_bytes: 10,
+            segment_size: 4,
+            fingerprint: fingerprint(None, b"0123"),
+            next_segment_index: 2,
+            finalized: false,
+            expires_after_secs: 86400,
+            initialized_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        store.save(state.clone()).await.unwrap();
+        assert_eq!(Some(state.clone()), store.load().await.unwrap());
+        assert!(!state.can_resume(10, 4, &fingerprint(None, b"0123")));
+        let state = UploadState {
+            initialized_at: SystemTime::now(),
+            ..state
+        };
+        assert!(state.can_resume(10, 4, &fingerprint(None, b"0123")));
+        assert!(!state.can_resume(10, 4, &fingerprint(None, b"abcd")));
+        assert!(!state.can_resume(10, 4, &fingerprint(Some("movie.mp4"), b"0123")));
+        store.clear().await.unwrap();
+        assert_eq!(None, store.load().await.unwrap());
+    }
+}
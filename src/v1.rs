@@ -1,7 +1,77 @@
-use reqwest::{multipart::Form, Error, Response};
+use futures::future::BoxFuture;
+use futures::Stream;
+use reqwest::{header::DATE, multipart::Form, Response, ResponseBuilderExt, StatusCode};
 use serde_json::Value;
-use std::time::Duration;
-use twapi_oauth::oauth1_authorization_header;
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, AtomicU8, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::endpoint::{self, AuthType};
+use crate::error::Error;
+use crate::oauth1::SignatureMethod;
+use crate::pagination::Paginator;
+use crate::sign::{now, to_header, Signer};
+use crate::stream::{
+    Framing, LegacyMessage, StreamEvent, StreamOptions, LEGACY_FILTER_STREAM_URL,
+    LEGACY_SAMPLE_STREAM_URL,
+};
+use crate::timeline::TimelineWalker;
+use crate::webhook::Webhook;
+
+const TIMESTAMP_OUT_OF_BOUNDS: i64 = 135;
+const VERIFY_CREDENTIALS_URL: &str = "https://api.twitter.com/1.1/account/verify_credentials.json";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum AccessLevel {
+    Read = 1,
+    ReadWrite = 2,
+    ReadWriteDirectMessages = 3,
+}
+
+impl AccessLevel {
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Self::Read),
+            "read-write" => Some(Self::ReadWrite),
+            "read-write-directmessages" => Some(Self::ReadWriteDirectMessages),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::ReadWrite => "read-write",
+            Self::ReadWriteDirectMessages => "read-write-directmessages",
+        }
+    }
+
+    // Direct message endpoints need DM access for any method, other writes need read-write.
+    pub fn required(method: &str, url: &str) -> Self {
+        if url.contains("/direct_messages") {
+            Self::ReadWriteDirectMessages
+        } else if method == "GET" || url.contains("/statuses/filter.json") {
+            Self::Read
+        } else {
+            Self::ReadWrite
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::Read),
+            2 => Some(Self::ReadWrite),
+            3 => Some(Self::ReadWriteDirectMessages),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Verified {
+    pub user: Value,
+    pub access_level: AccessLevel,
+}
 
 pub struct Client {
     consumer_key: String,
@@ -9,6 +79,8 @@ pub struct Client {
     access_key: String,
     access_secret: String,
     timeout_sec: Option<Duration>,
+    time_offset: AtomicI64,
+    access_level: AtomicU8,
 }
 
 impl Client {
@@ -25,6 +97,8 @@ impl Client {
             access_key: access_key.to_owned(),
             access_secret: access_secret.to_owned(),
             timeout_sec,
+            time_offset: AtomicI64::new(0),
+            access_level: AtomicU8::new(0),
         }
     }
 
@@ -35,19 +109,116 @@ impl Client {
             access_key: std::env::var("ACCESS_KEY")?,
             access_secret: std::env::var("ACCESS_SECRET")?,
             timeout_sec: None,
+            time_offset: AtomicI64::new(0),
+            access_level: AtomicU8::new(0),
         })
     }
 
+    // Seconds added to the local clock for oauth_timestamp, learned from the server Date header.
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed)
+    }
+
+    // Last x-access-level seen on a response, None until the first call.
+    pub fn access_level(&self) -> Option<AccessLevel> {
+        AccessLevel::from_u8(self.access_level.load(Ordering::Relaxed))
+    }
+
+    pub async fn verify(&self) -> Result<Verified, Error> {
+        self.verify_url(VERIFY_CREDENTIALS_URL).await
+    }
+
+    async fn verify_url(&self, url: &str) -> Result<Verified, Error> {
+        let response = self.get(url, &vec![]).await?;
+        let response = crate::check_status(response).await?;
+        // A missing header is treated as the least privilege.
+        let access_level = access_level_header(&response).unwrap_or(AccessLevel::Read);
+        Ok(Verified {
+            user: response.json().await?,
+            access_level,
+        })
+    }
+
+    pub fn paginate(&self, url: &str, query_options: &Vec<(&str, &str)>) -> Paginator<'_, Self> {
+        Paginator::new(self, url, query_options)
+    }
+
+    pub fn paginate_cursor(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Paginator<'_, Self> {
+        Paginator::new_cursor(self, url, query_options)
+    }
+
+    pub fn timeline(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> TimelineWalker<'_, Self> {
+        TimelineWalker::new(self, url, query_options)
+    }
+
+    pub fn webhook(&self) -> Webhook {
+        Webhook::new(&self.consumer_secret)
+    }
+
+    fn check_access_level(&self, method: &str, url: &str) -> Result<(), Error> {
+        let required = AccessLevel::required(method, url);
+        match self.access_level() {
+            Some(actual) if actual < required => {
+                Err(Error::InsufficientAccess { required, actual })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn record_access_level(&self, response: &Response) {
+        if let Some(access_level) = access_level_header(response) {
+            self.access_level
+                .store(access_level as u8, Ordering::Relaxed);
+        }
+    }
+
     fn calc_oauth(&self, method: &str, url: &str, query_options: &Vec<(&str, &str)>) -> String {
-        oauth1_authorization_header(
-            &self.consumer_key,
-            &self.consumer_secret,
-            &self.access_key,
-            &self.access_secret,
+        let signer = Signer {
+            consumer_key: &self.consumer_key,
+            consumer_secret: &self.consumer_secret,
+            token_secret: &self.access_secret,
+            signature_method: &SignatureMethod::HmacSha1,
+        };
+        to_header(&signer.parameters(
+            &vec![("oauth_token", &self.access_key)],
             method,
             url,
-            &query_options,
-        )
+            query_options,
+            now() + self.time_offset(),
+        ))
+    }
+
+    // Sends the request and, when Twitter rejects oauth_timestamp (code 135),
+    // corrects the clock offset from the Date header and retries once.
+    async fn execute<F, Fut>(
+        &self,
+        method: &str,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        send: F,
+    ) -> Result<Response, Error>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Response, reqwest::Error>>,
+    {
+        endpoint::check(method, url, AuthType::OAuth1User, &[])?;
+        self.check_access_level(method, url)?;
+        let response = send(self.calc_oauth(method, url, query_options)).await?;
+        self.record_access_level(&response);
+        if let Some(response) = check_timestamp(response, &self.time_offset).await? {
+            return Ok(response);
+        }
+        let response = send(self.calc_oauth(method, url, query_options)).await?;
+        self.record_access_level(&response);
+        Ok(response)
     }
 
     pub async fn get(
@@ -55,8 +226,20 @@ impl Client {
         url: &str,
         query_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        let authorization = self.calc_oauth("GET", url, &query_options);
-        crate::raw::get(url, query_options, &authorization, self.timeout_sec).await
+        self.get_with_timeout(url, query_options, self.timeout_sec)
+            .await
+    }
+
+    async fn get_with_timeout(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        timeout_sec: Option<Duration>,
+    ) -> Result<Response, Error> {
+        self.execute("GET", url, query_options, |authorization| async move {
+            crate::raw::get(url, query_options, &authorization, timeout_sec).await
+        })
+        .await
     }
 
     pub async fn post(
@@ -64,30 +247,121 @@ impl Client {
         url: &str,
         query_options: &Vec<(&str, &str)>,
         form_options: &Vec<(&str, &str)>,
+    ) -> Result<Response, Error> {
+        self.post_with_timeout(url, query_options, form_options, self.timeout_sec)
+            .await
+    }
+
+    async fn post_with_timeout(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        form_options: &Vec<(&str, &str)>,
+        timeout_sec: Option<Duration>,
     ) -> Result<Response, Error> {
         let mut merged_options = query_options.clone();
         for option in form_options {
             merged_options.push(*option);
         }
-        let authorization = self.calc_oauth("POST", url, &merged_options);
-        crate::raw::post(
-            url,
-            query_options,
-            form_options,
-            &authorization,
-            self.timeout_sec,
-        )
+        self.execute("POST", url, &merged_options, |authorization| async move {
+            crate::raw::post(
+                url,
+                query_options,
+                form_options,
+                &authorization,
+                timeout_sec,
+            )
+            .await
+        })
         .await
     }
 
+    // POST streaming with delimited=length and stall_warnings, read without timeout_sec.
+    pub fn stream<'a>(
+        &'a self,
+        url: &str,
+        form_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<LegacyMessage>, Error>> + 'a {
+        self.legacy_stream("POST", url, form_options, options)
+    }
+
+    // Same as stream, with the options sent in the query of a GET.
+    pub fn get_stream<'a>(
+        &'a self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<LegacyMessage>, Error>> + 'a {
+        self.legacy_stream("GET", url, query_options, options)
+    }
+
+    fn legacy_stream<'a>(
+        &'a self,
+        method: &'static str,
+        url: &str,
+        options: &Vec<(&str, &str)>,
+        stream_options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<LegacyMessage>, Error>> + 'a {
+        let url = url.to_owned();
+        let mut options: Vec<(String, String)> = options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        options.push(("delimited".to_owned(), "length".to_owned()));
+        options.push(("stall_warnings".to_owned(), "true".to_owned()));
+        // v1.1 has no backfill_minutes.
+        let connector = Box::new(move |_| {
+            let url = url.clone();
+            let options = options.clone();
+            Box::pin(async move {
+                let options: Vec<(&str, &str)> = options
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect();
+                if method == "GET" {
+                    self.get_with_timeout(&url, &options, None).await
+                } else {
+                    self.post_with_timeout(&url, &vec![], &options, None).await
+                }
+            }) as BoxFuture<'a, Result<Response, Error>>
+        });
+        crate::stream::consume(
+            connector,
+            Framing::DelimitedLength,
+            Box::new(|json| Ok(LegacyMessage::from_json(json))),
+            stream_options,
+        )
+    }
+
+    // track, follow and locations go to form_options.
+    pub fn filter_stream<'a>(
+        &'a self,
+        form_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<LegacyMessage>, Error>> + 'a {
+        self.stream(LEGACY_FILTER_STREAM_URL, form_options, options)
+    }
+
+    // GET statuses/sample.json, query_options such as language.
+    pub fn sample_stream<'a>(
+        &'a self,
+        query_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<LegacyMessage>, Error>> + 'a {
+        self.get_stream(LEGACY_SAMPLE_STREAM_URL, query_options, options)
+    }
+
     pub async fn json(
         &self,
         url: &str,
         query_options: &Vec<(&str, &str)>,
         data: &Value,
     ) -> Result<Response, Error> {
-        let authorization = self.calc_oauth("POST", url, &query_options);
-        crate::raw::json(url, query_options, data, &authorization, self.timeout_sec).await
+        self.execute("POST", url, query_options, |authorization| async move {
+            crate::raw::json(url, query_options, data, &authorization, self.timeout_sec).await
+        })
+        .await
     }
 
     pub async fn put(
@@ -95,8 +369,10 @@ impl Client {
         url: &str,
         query_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        let authorization = self.calc_oauth("PUT", url, &query_options);
-        crate::raw::put(url, query_options, &authorization, self.timeout_sec).await
+        self.execute("PUT", url, query_options, |authorization| async move {
+            crate::raw::put(url, query_options, &authorization, self.timeout_sec).await
+        })
+        .await
     }
 
     pub async fn delete(
@@ -104,8 +380,10 @@ impl Client {
         url: &str,
         query_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        let authorization = self.calc_oauth("DELETE", url, &query_options);
-        crate::raw::delete(url, query_options, &authorization, self.timeout_sec).await
+        self.execute("DELETE", url, query_options, |authorization| async move {
+            crate::raw::delete(url, query_options, &authorization, self.timeout_sec).await
+        })
+        .await
     }
 
     pub async fn multipart(
@@ -114,8 +392,77 @@ impl Client {
         query_options: &Vec<(&str, &str)>,
         data: Form,
     ) -> Result<Response, Error> {
-        let authorization = self.calc_oauth("POST", url, &query_options);
-        crate::raw::multipart(url, query_options, data, &authorization, self.timeout_sec).await
+        // Form can not be replayed, so multipart only benefits from an offset learned earlier.
+        endpoint::check("POST", url, AuthType::OAuth1User, &[])?;
+        self.check_access_level("POST", url)?;
+        let authorization = self.calc_oauth("POST", url, query_options);
+        let response =
+            crate::raw::multipart(url, query_options, data, &authorization, self.timeout_sec)
+                .await?;
+        self.record_access_level(&response);
+        Ok(response)
+    }
+}
+
+fn server_time(response: &Response) -> Option<i64> {
+    let date = response.headers().get(DATE)?.to_str().ok()?;
+    let time = httpdate::parse_http_date(date).ok()?;
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
+
+fn access_level_header(response: &Response) -> Option<AccessLevel> {
+    response
+        .headers()
+        .get("x-access-level")
+        .and_then(|it| it.to_str().ok())
+        .and_then(AccessLevel::from_header)
+}
+
+// None when Twitter rejected oauth_timestamp (code 135) and time_offset was corrected from
+// the Date header, so the request should be signed and sent again.
+pub(crate) async fn check_timestamp(
+    response: Response,
+    time_offset: &AtomicI64,
+) -> Result<Option<Response>, Error> {
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(Some(response));
+    }
+    // Without a Date header there is nothing to correct, keep the response as is.
+    let offset = match server_time(&response) {
+        Some(server_time) => server_time - now(),
+        None => return Ok(Some(response)),
+    };
+    let status = response.status();
+    let version = response.version();
+    let response_url = response.url().clone();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    if is_timestamp_out_of_bounds(&body) {
+        time_offset.store(offset, Ordering::Relaxed);
+        return Ok(None);
+    }
+    // The body was read to look for code 135, hand back a copy of the response.
+    let mut builder = http::Response::builder()
+        .status(status)
+        .version(version)
+        .url(response_url);
+    if let Some(builder_headers) = builder.headers_mut() {
+        *builder_headers = headers;
+    }
+    Ok(Some(Response::from(builder.body(body).unwrap())))
+}
+
+fn is_timestamp_out_of_bounds(body: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(json) => json["errors"]
+            .as_array()
+            .map(|errors| {
+                errors
+                    .iter()
+                    .any(|error| error["code"].as_i64() == Some(TIMESTAMP_OUT_OF_BOUNDS))
+            })
+            .unwrap_or(false),
+        Err(_) => false,
     }
 }
 
@@ -238,9 +585,123 @@ pub async fn multipart(
 
 #[cfg(test)]
 mod tests {
+    use crate::error::Error;
+    use crate::test_server::{json_response, serve};
     use crate::*;
     use serde_json::Value;
     use std::env;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_access_level_required() {
+        let url = "https://api.twitter.com/1.1/direct_messages/events/list.json";
+        assert_eq!(
+            v1::AccessLevel::ReadWriteDirectMessages,
+            v1::AccessLevel::required("GET", url)
+        );
+        let url = "https://api.twitter.com/1.1/statuses/update.json";
+        assert_eq!(
+            v1::AccessLevel::ReadWrite,
+            v1::AccessLevel::required("POST", url)
+        );
+        assert!(v1::AccessLevel::Read < v1::AccessLevel::ReadWrite);
+        assert_eq!(
+            Some(v1::AccessLevel::ReadWriteDirectMessages),
+            v1::AccessLevel::from_header("read-write-directmessages")
+        );
+    }
+
+    #[test]
+    fn test_timestamp_out_of_bounds() {
+        assert!(super::is_timestamp_out_of_bounds(
+            br#"{"errors":[{"code":135,"message":"Timestamp out of bounds."}]}"#
+        ));
+        assert!(!super::is_timestamp_out_of_bounds(
+            br#"{"errors":[{"code":32,"message":"Could not authenticate you."}]}"#
+        ));
+        assert!(!super::is_timestamp_out_of_bounds(b"Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn test_access_level() {
+        let (base, requests) = serve(|request| {
+            if request.starts_with("GET /1.1/account/settings.json") {
+                json_response("200 OK", &[("x-access-level", "read-write")], "{}")
+            } else if request.starts_with("GET /1.1/statuses/home_timeline.json") {
+                json_response("200 OK", &[("x-access-level", "read")], "[]")
+            } else {
+                json_response("200 OK", &[], r#"{"id_str":"1"}"#)
+            }
+        })
+        .await;
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/account/settings.json", base);
+        client.get(&url, &vec![]).await.unwrap();
+        assert_eq!(Some(v1::AccessLevel::ReadWrite), client.access_level());
+
+        let url = format!("{}/1.1/account/verify_credentials.json", base);
+        let verified = client.verify_url(&url).await.unwrap();
+        assert_eq!(v1::AccessLevel::Read, verified.access_level);
+        assert_eq!("1", verified.user["id_str"]);
+
+        let url = format!("{}/1.1/statuses/home_timeline.json", base);
+        client.get(&url, &vec![]).await.unwrap();
+        assert_eq!(Some(v1::AccessLevel::Read), client.access_level());
+        let url = format!("{}/1.1/statuses/update.json", base);
+        let result = client.post(&url, &vec![], &vec![("status", "hello")]).await;
+        assert!(matches!(
+            result,
+            Err(Error::InsufficientAccess {
+                required: v1::AccessLevel::ReadWrite,
+                actual: v1::AccessLevel::Read,
+            })
+        ));
+        assert_eq!(3, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_retry() {
+        let calls = AtomicUsize::new(0);
+        let (base, requests) = serve(move |_| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                json_response(
+                    "401 Unauthorized",
+                    &[("date", "Sun, 06 Nov 2033 08:49:37 GMT")],
+                    r#"{"errors":[{"code":135,"message":"Timestamp out of bounds."}]}"#,
+                )
+            } else {
+                json_response("200 OK", &[], "{}")
+            }
+        })
+        .await;
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/account/settings.json", base);
+        let response = client.get(&url, &vec![]).await.unwrap();
+        assert_eq!(200, response.status().as_u16());
+        assert_eq!(2, requests.lock().unwrap().len());
+        assert!(client.time_offset() > 0);
+
+        let (base, requests) = serve(|_| {
+            json_response(
+                "401 Unauthorized",
+                &[("date", "Sun, 06 Nov 2033 08:49:37 GMT")],
+                r#"{"errors":[{"code":32,"message":"Could not authenticate you."}]}"#,
+            )
+        })
+        .await;
+        let client = v1::Client::new("a", "b", "c", "d", None);
+        let url = format!("{}/1.1/account/settings.json", base);
+        let response = client.get(&url, &vec![]).await.unwrap();
+        assert_eq!(401, response.status().as_u16());
+        assert_eq!(url, response.url().as_str());
+        assert_eq!(1, requests.lock().unwrap().len());
+        assert_eq!(0, client.time_offset());
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("Could not authenticate"));
+    }
 
     #[tokio::test]
     async fn test_api() {
@@ -1,34 +1,79 @@
-use reqwest::{multipart::Form, Error, Response};
+use futures::{Stream, StreamExt};
+use reqwest::{multipart::Form, Response};
 use serde_json::Value;
 use std::time::Duration;
 use twapi_oauth::oauth2_authorization_header;
 
+use crate::checkpoint::CheckpointStore;
+use crate::compliance::ComplianceEvent;
+use crate::endpoint::{self, AuthType};
+use crate::error::Error;
+use crate::pagination::Paginator;
+use crate::rules::StreamRules;
+use crate::stream::{
+    Framing, Parser, StreamEvent, StreamOptions, FILTERED_STREAM_URL, SAMPLE10_PARTITIONS,
+    SAMPLE10_STREAM_URL, SAMPLE_STREAM_URL,
+};
+use crate::timeline::{Style, TimelineWalker};
+
 pub struct Client {
     bearer_token: String,
     timeout_sec: Option<Duration>,
+    auth_type: Option<AuthType>,
+    scopes: Vec<String>,
 }
 
 impl Client {
+    // Any bearer token, app-only or OAuth 2.0 user context, sent without the endpoint check.
     pub fn new(bearer_token: &str, timeout_sec: Option<Duration>) -> Self {
         Self {
             bearer_token: bearer_token.to_owned(),
             timeout_sec,
+            auth_type: None,
+            scopes: vec![],
+        }
+    }
+
+    // App-only bearer token, endpoints without app-only support are rejected locally.
+    pub fn new_app_only(bearer_token: &str, timeout_sec: Option<Duration>) -> Self {
+        Self {
+            auth_type: Some(AuthType::AppOnly),
+            ..Self::new(bearer_token, timeout_sec)
         }
     }
 
+    // OAuth 2.0 Authorization Code with PKCE token. Empty scopes skip the scope check.
+    pub fn new_user_context(
+        bearer_token: &str,
+        scopes: &[&str],
+        timeout_sec: Option<Duration>,
+    ) -> Self {
+        Self {
+            bearer_token: bearer_token.to_owned(),
+            timeout_sec,
+            auth_type: Some(AuthType::OAuth2User),
+            scopes: scopes.iter().map(|it| it.to_string()).collect(),
+        }
+    }
+
+    // None for Client::new.
+    pub fn auth_type(&self) -> Option<AuthType> {
+        self.auth_type
+    }
+
     pub async fn new_from_key(
         consumer_key: &str,
         consumer_secret: &str,
         timeout_sec: Option<Duration>,
-    ) -> Result<Option<Self>, Error> {
+    ) -> Result<Option<Self>, reqwest::Error> {
         Ok(
             crate::oauth::get_bearer_token(&consumer_key, &consumer_secret, timeout_sec)
                 .await?
-                .map(|bearer_token| Self::new(&bearer_token, None)),
+                .map(|bearer_token| Self::new_app_only(&bearer_token, None)),
         )
     }
 
-    pub async fn new_by_env(timeout_sec: Option<Duration>) -> Result<Option<Self>, Error> {
+    pub async fn new_by_env(timeout_sec: Option<Duration>) -> Result<Option<Self>, reqwest::Error> {
         let consumer_key = match std::env::var("CONSUMER_KEY") {
             Ok(consumer_key) => consumer_key,
             Err(_) => return Ok(None),
@@ -44,12 +89,189 @@ impl Client {
         oauth2_authorization_header(&self.bearer_token)
     }
 
+    pub fn paginate(&self, url: &str, query_options: &Vec<(&str, &str)>) -> Paginator<'_, Self> {
+        Paginator::new(self, url, query_options)
+    }
+
+    pub fn paginate_cursor(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> Paginator<'_, Self> {
+        Paginator::new_cursor(self, url, query_options)
+    }
+
+    pub fn timeline(
+        &self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+    ) -> TimelineWalker<'_, Self> {
+        TimelineWalker::new(self, url, query_options).with_style(Style::UntilId)
+    }
+
+    // Streams are read without timeout_sec, stalls are detected by StreamOptions::stall_timeout.
+    pub fn stream<'a>(
+        &'a self,
+        url: &str,
+        query_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<Value>, Error>> + 'a {
+        self.stream_with(url, query_options, options, Box::new(Ok))
+    }
+
+    fn stream_with<'a, T: Send + 'a>(
+        &'a self,
+        url: &str,
+        query_options: &[(&str, &str)],
+        options: StreamOptions,
+        parser: Parser<T>,
+    ) -> impl Stream<Item = Result<StreamEvent<T>, Error>> + 'a {
+        let url = url.to_owned();
+        let query_options: Vec<(String, String)> = query_options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let connector = Box::new(move |backfill: Option<u32>| {
+            let url = url.clone();
+            let query_options = query_options.clone();
+            Box::pin(async move {
+                self.check_endpoint("GET", &url)?;
+                let backfill = backfill.map(|it| it.to_string());
+                let mut query_options: Vec<(&str, &str)> = query_options
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect();
+                if let Some(backfill) = &backfill {
+                    query_options.push(("backfill_minutes", backfill));
+                }
+                Ok(crate::raw::get(&url, &query_options, &self.make_header(), None).await?)
+            }) as futures::future::BoxFuture<'a, Result<Response, Error>>
+        });
+        crate::stream::consume(connector, Framing::Lines, parser, options)
+    }
+
+    // Resumes from the stored checkpoint with backfill_minutes sized to the outage
+    // and skips tweets that were already delivered.
+    pub fn checkpointed_stream<'a, S: CheckpointStore>(
+        &'a self,
+        url: &str,
+        query_options: &[(&str, &str)],
+        options: StreamOptions,
+        store: &'a S,
+    ) -> impl Stream<Item = Result<StreamEvent<Value>, Error>> + 'a {
+        let url = url.to_owned();
+        let query_options: Vec<(String, String)> = query_options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        futures::stream::once(store.load()).flat_map(move |checkpoint| match checkpoint {
+            Ok(checkpoint) => {
+                let options = StreamOptions {
+                    backfill_on_reconnect: true,
+                    resume_from: checkpoint.map(|it| it.received_at),
+                    ..options.clone()
+                };
+                let query_options: Vec<(&str, &str)> = query_options
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect();
+                let events = self.stream(&url, &query_options, options).boxed();
+                crate::checkpoint::track(events, store, checkpoint).boxed()
+            }
+            Err(err) => futures::stream::iter(vec![Err(err)]).boxed(),
+        })
+    }
+
+    // TWEETS_COMPLIANCE_STREAM_URL or USERS_COMPLIANCE_STREAM_URL,
+    // partition is 1 to COMPLIANCE_PARTITIONS.
+    pub fn compliance_stream<'a>(
+        &'a self,
+        url: &str,
+        partition: u32,
+        query_options: &[(&str, &str)],
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<ComplianceEvent>, Error>> + 'a {
+        let partition = partition.to_string();
+        let mut query_options = query_options.to_vec();
+        query_options.push(("partition", &partition));
+        self.stream_with(
+            url,
+            &query_options,
+            options,
+            Box::new(|json| Ok(ComplianceEvent::from_json(json))),
+        )
+    }
+
+    pub fn stream_rules(&self) -> StreamRules<'_> {
+        StreamRules::new(self)
+    }
+
+    pub fn filtered_stream<'a>(
+        &'a self,
+        query_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<Value>, Error>> + 'a {
+        self.stream(FILTERED_STREAM_URL, query_options, options)
+    }
+
+    pub fn sample_stream<'a>(
+        &'a self,
+        query_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<StreamEvent<Value>, Error>> + 'a {
+        self.stream(SAMPLE_STREAM_URL, query_options, options)
+    }
+
+    // partitions are 1 to SAMPLE10_PARTITIONS, an empty slice reads all of them.
+    pub fn sample10_stream<'a>(
+        &'a self,
+        partitions: &[u32],
+        query_options: &Vec<(&str, &str)>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = (u32, Result<StreamEvent<Value>, Error>)> + 'a {
+        let partitions = if partitions.is_empty() {
+            (1..=SAMPLE10_PARTITIONS).collect()
+        } else {
+            partitions.to_vec()
+        };
+        self.partitioned_stream(SAMPLE10_STREAM_URL, &partitions, query_options, options)
+    }
+
+    // Connects to every partition at once and yields events in arrival order,
+    // tagged with the partition they came from.
+    pub fn partitioned_stream<'a>(
+        &'a self,
+        url: &str,
+        partitions: &[u32],
+        query_options: &[(&str, &str)],
+        options: StreamOptions,
+    ) -> impl Stream<Item = (u32, Result<StreamEvent<Value>, Error>)> + 'a {
+        let streams = partitions.iter().map(|partition| {
+            let partition = *partition;
+            let value = partition.to_string();
+            let mut query_options = query_options.to_vec();
+            query_options.push(("partition", &value));
+            self.stream(url, &query_options, options.clone())
+                .map(move |event| (partition, event))
+                .boxed()
+        });
+        futures::stream::select_all(streams)
+    }
+
+    fn check_endpoint(&self, method: &str, url: &str) -> Result<(), Error> {
+        match self.auth_type {
+            Some(auth_type) => endpoint::check(method, url, auth_type, &self.scopes),
+            None => Ok(()),
+        }
+    }
+
     pub async fn get(
         &self,
         url: &str,
         query_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        crate::raw::get(url, query_options, &self.make_header(), self.timeout_sec).await
+        self.check_endpoint("GET", url)?;
+        Ok(crate::raw::get(url, query_options, &self.make_header(), self.timeout_sec).await?)
     }
 
     pub async fn post(
@@ -58,14 +280,15 @@ impl Client {
         query_options: &Vec<(&str, &str)>,
         form_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        crate::raw::post(
+        self.check_endpoint("POST", url)?;
+        Ok(crate::raw::post(
             url,
             query_options,
             form_options,
             &self.make_header(),
             self.timeout_sec,
         )
-        .await
+        .await?)
     }
 
     pub async fn json(
@@ -74,14 +297,15 @@ impl Client {
         query_options: &Vec<(&str, &str)>,
         data: &Value,
     ) -> Result<Response, Error> {
-        crate::raw::json(
+        self.check_endpoint("POST", url)?;
+        Ok(crate::raw::json(
             url,
             query_options,
             data,
             &self.make_header(),
             self.timeout_sec,
         )
-        .await
+        .await?)
     }
 
     pub async fn put(
@@ -89,7 +313,8 @@ impl Client {
         url: &str,
         query_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        crate::raw::put(url, query_options, &self.make_header(), self.timeout_sec).await
+        self.check_endpoint("PUT", url)?;
+        Ok(crate::raw::put(url, query_options, &self.make_header(), self.timeout_sec).await?)
     }
 
     pub async fn delete(
@@ -97,7 +322,8 @@ impl Client {
         url: &str,
         query_options: &Vec<(&str, &str)>,
     ) -> Result<Response, Error> {
-        crate::raw::delete(url, query_options, &self.make_header(), self.timeout_sec).await
+        self.check_endpoint("DELETE", url)?;
+        Ok(crate::raw::delete(url, query_options, &self.make_header(), self.timeout_sec).await?)
     }
 
     pub async fn multipart(
@@ -106,14 +332,15 @@ impl Client {
         query_options: &Vec<(&str, &str)>,
         data: Form,
     ) -> Result<Response, Error> {
-        crate::raw::multipart(
+        self.check_endpoint("POST", url)?;
+        Ok(crate::raw::multipart(
             url,
             query_options,
             data,
             &self.make_header(),
             self.timeout_sec,
         )
-        .await
+        .await?)
     }
 }
 
@@ -182,6 +409,7 @@ pub async fn multipart(
 
 #[cfg(test)]
 mod tests {
+    use crate::test_server::{json_response, serve};
     use crate::*;
     use serde_json::Value;
     use std::env;
@@ -209,4 +437,20 @@ mod tests {
         .unwrap();
         println!("{:?}", res);
     }
+
+    #[tokio::test]
+    async fn test_auth_type() {
+        let (base, requests) = serve(|_| json_response("200 OK", &[], r#"{"data":{}}"#)).await;
+        let url = format!("{}/2/users/me", base);
+        v2::Client::new("user token", None)
+            .get(&url, &vec![])
+            .await
+            .unwrap();
+        v2::get(&url, &vec![], "user token", None).await.unwrap();
+        let result = v2::Client::new_app_only("app token", None)
+            .get(&url, &vec![])
+            .await;
+        assert!(matches!(result, Err(Error::UnsupportedAuthType { .. })));
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
 }
@@ -0,0 +1,402 @@
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use http::header::CONTENT_TYPE;
+use http::{Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use sha2::Sha256;
+#[cfg(any(feature = "axum", feature = "hyper"))]
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::error::Error;
+
+pub const SIGNATURE_HEADER: &str = "x-twitter-webhooks-signature";
+
+// follow/unfollow, block/unblock and mute/unmute events.
+#[derive(Clone, Debug)]
+pub struct UserAction {
+    pub action: String,
+    pub created_timestamp: String,
+    pub source: Value,
+    pub target: Value,
+}
+
+impl UserAction {
+    fn from_json(json: &Value) -> Self {
+        Self {
+            action: string_of(json, "type"),
+            created_timestamp: string_of(json, "created_timestamp"),
+            source: json["source"].clone(),
+            target: json["target"].clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ActivityEvent {
+    TweetCreate(Value),
+    Favorite(Value),
+    Follow(UserAction),
+    Block(UserAction),
+    Mute(UserAction),
+    // The message_create event, senders and recipients are in ActivityPayload::users.
+    DirectMessage(Value),
+    DirectMessageIndicateTyping {
+        sender_id: String,
+        recipient_id: String,
+        created_timestamp: String,
+    },
+    TweetDelete {
+        tweet_id: String,
+        user_id: String,
+        timestamp_ms: String,
+    },
+    // Token revocation, {"revoke": {...}}.
+    UserEvent(Value),
+    Unknown {
+        key: String,
+        event: Value,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct ActivityPayload {
+    pub for_user_id: String,
+    pub events: Vec<ActivityEvent>,
+    pub users: Value,
+}
+
+impl ActivityPayload {
+    pub fn from_json(json: &Value) -> Self {
+        let mut events = vec![];
+        if let Some(object) = json.as_object() {
+            for (key, value) in object {
+                if matches!(
+                    key.as_str(),
+                    "for_user_id" | "users" | "apps" | "user_has_blocked" | "source"
+                ) {
+                    continue;
+                }
+                let items = match value {
+                    Value::Array(items) => items.clone(),
+                    value => vec![value.clone()],
+                };
+                events.extend(items.iter().map(|item| event_of(key, item)));
+            }
+        }
+        Self {
+            for_user_id: string_of(json, "for_user_id"),
+            events,
+            users: json["users"].clone(),
+        }
+    }
+}
+
+fn string_of(json: &Value, key: &str) -> String {
+    json[key].as_str().unwrap_or_default().to_owned()
+}
+
+fn event_of(key: &str, item: &Value) -> ActivityEvent {
+    match key {
+        "tweet_create_events" => ActivityEvent::TweetCreate(item.clone()),
+        "favorite_events" => ActivityEvent::Favorite(item.clone()),
+        "follow_events" => ActivityEvent::Follow(UserAction::from_json(item)),
+        "block_events" => ActivityEvent::Block(UserAction::from_json(item)),
+        "mute_events" => ActivityEvent::Mute(UserAction::from_json(item)),
+        "direct_message_events" => ActivityEvent::DirectMessage(item.clone()),
+        "direct_message_indicate_typing_events" => ActivityEvent::DirectMessageIndicateTyping {
+            sender_id: string_of(item, "sender_id"),
+            recipient_id: string_of(&item["target"], "recipient_id"),
+            created_timestamp: string_of(item, "created_timestamp"),
+        },
+        "tweet_delete_events" => ActivityEvent::TweetDelete {
+            tweet_id: string_of(&item["status"], "id"),
+            user_id: string_of(&item["status"], "user_id"),
+            timestamp_ms: string_of(item, "timestamp_ms"),
+        },
+        "user_event" => ActivityEvent::UserEvent(item.clone()),
+        _ => ActivityEvent::Unknown {
+            key: key.to_owned(),
+            event: item.clone(),
+        },
+    }
+}
+
+// Account Activity API webhook helpers keyed with the app's consumer secret.
+pub struct Webhook {
+    consumer_secret: String,
+}
+
+impl Webhook {
+    pub fn new(consumer_secret: &str) -> Self {
+        Self {
+            consumer_secret: consumer_secret.to_owned(),
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.consumer_secret.as_bytes()).unwrap();
+        mac.update(message);
+        format!("sha256={}", base64::encode(mac.finalize().into_bytes()))
+    }
+
+    // Body for the GET crc_token challenge.
+    pub fn crc_response(&self, crc_token: &str) -> Value {
+        json!({ "response_token": self.sign(crc_token.as_bytes()) })
+    }
+
+    pub fn signature(&self, body: &[u8]) -> String {
+        self.sign(body)
+    }
+
+    pub fn verify(&self, body: &[u8], signature: &str) -> Result<(), Error> {
+        let expected = self.sign(body);
+        if bool::from(expected.as_bytes().ct_eq(signature.trim().as_bytes())) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+
+    // Verifies the x-twitter-webhooks-signature value, then parses the POST body.
+    pub fn parse(&self, body: &[u8], signature: &str) -> Result<ActivityPayload, Error> {
+        self.verify(body, signature)?;
+        let json: Value = serde_json::from_slice(body).map_err(Error::Json)?;
+        Ok(ActivityPayload::from_json(&json))
+    }
+}
+
+type Callback = Box<dyn Fn(ActivityPayload) + Send + Sync>;
+
+// Answers the CRC GET and verified event POSTs on one route, independent of the
+// HTTP framework. Enable the axum or hyper feature for ready-made services.
+pub struct WebhookHandler {
+    webhook: Webhook,
+    callback: Callback,
+}
+
+impl WebhookHandler {
+    pub fn new<F>(webhook: Webhook, callback: F) -> Self
+    where
+        F: Fn(ActivityPayload) + Send + Sync + 'static,
+    {
+        Self {
+            webhook,
+            callback: Box::new(callback),
+        }
+    }
+
+    pub fn handle(&self, request: Request<Bytes>) -> Response<Bytes> {
+        match *request.method() {
+            Method::GET => {
+                let crc_token = request.uri().query().and_then(|query| {
+                    serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+                        .ok()?
+                        .into_iter()
+                        .find(|(key, _)| key == "crc_token")
+                        .map(|(_, value)| value)
+                });
+                match crc_token {
+                    Some(crc_token) => {
+                        let body = self.webhook.crc_response(&crc_token).to_string();
+                        response(StatusCode::OK, Some("application/json"), body.into())
+                    }
+                    None => response(StatusCode::BAD_REQUEST, None, Bytes::new()),
+                }
+            }
+            Method::POST => {
+                let signature = request
+                    .headers()
+                    .get(SIGNATURE_HEADER)
+                    .and_then(|it| it.to_str().ok())
+                    .unwrap_or_default();
+                match self.webhook.parse(request.body(), signature) {
+                    Ok(payload) => {
+                        (self.callback)(payload);
+                        response(StatusCode::OK, None, Bytes::new())
+                    }
+                    Err(Error::InvalidSignature) => {
+                        response(StatusCode::UNAUTHORIZED, None, Bytes::new())
+                    }
+                    Err(_) => response(StatusCode::BAD_REQUEST, None, Bytes::new()),
+                }
+            }
+            _ => response(StatusCode::METHOD_NOT_ALLOWED, None, Bytes::new()),
+        }
+    }
+}
+
+fn response(status: StatusCode, content_type: Option<&str>, body: Bytes) -> Response<Bytes> {
+    let mut builder = Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+    builder.body(body).unwrap()
+}
+
+#[cfg(feature = "axum")]
+impl WebhookHandler {
+    // Router serving GET and POST on path.
+    pub fn into_router(self, path: &str) -> axum::Router {
+        let handler = Arc::new(self);
+        let route = move |method: Method, uri: http::Uri, headers: http::HeaderMap, body: Bytes| {
+            let handler = handler.clone();
+            async move {
+                let mut request = Request::new(body);
+                *request.method_mut() = method;
+                *request.uri_mut() = uri;
+                *request.headers_mut() = headers;
+                handler.handle(request).map(axum::body::Full::from)
+            }
+        };
+        axum::Router::new().route(path, axum::routing::get(route.clone()).post(route))
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl WebhookHandler {
+    pub async fn handle_hyper(
+        &self,
+        request: Request<hyper::Body>,
+    ) -> Result<Response<hyper::Body>, hyper::Error> {
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        Ok(self
+            .handle(Request::from_parts(parts, body))
+            .map(hyper::Body::from))
+    }
+
+    // Serves every path of the listener until the server fails.
+    pub async fn serve(self, listener: std::net::TcpListener) -> Result<(), hyper::Error> {
+        let handler = Arc::new(self);
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let handler = handler.clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(move |request| {
+                    let handler = handler.clone();
+                    async move { handler.handle_hyper(request).await }
+                }))
+            }
+        });
+        hyper::Server::from_tcp(listener)?.serve(make_service).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::webhook::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_crc_response() {
+        let webhook = Webhook::new("secret");
+        assert_eq!(
+            "sha256=oeUF6Wxqoezggrue+wbIDxKRPSF6esKwizR2MHh9HaA=",
+            webhook.crc_response("challenge")["response_token"]
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let webhook = Webhook::new("secret");
+        let body = br#"{"for_user_id":"1","follow_events":[{"type":"unfollow","created_timestamp":"1517588749178","target":{"id":"2"},"source":{"id":"1"}}],"tweet_delete_events":[{"status":{"id":"3","user_id":"1"},"timestamp_ms":"1517588749178"}]}"#;
+        match webhook.parse(body, "sha256=invalid") {
+            Err(Error::InvalidSignature) => {}
+            other => panic!("{:?}", other),
+        }
+        let payload = webhook.parse(body, &webhook.signature(body)).unwrap();
+        assert_eq!("1", payload.for_user_id);
+        assert_eq!(2, payload.events.len());
+        assert!(payload.events.iter().any(|it| matches!(
+            it,
+            ActivityEvent::Follow(action) if action.action == "unfollow"
+        )));
+        assert!(payload.events.iter().any(|it| matches!(
+            it,
+            ActivityEvent::TweetDelete { tweet_id, .. } if tweet_id == "3"
+        )));
+    }
+
+    #[test]
+    fn test_handler() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let captured = received.clone();
+        let handler = WebhookHandler::new(Webhook::new("secret"), move |payload| {
+            captured.lock().unwrap().push(payload.for_user_id)
+        });
+        let request = Request::get("/webhook?crc_token=challenge")
+            .body(Bytes::new())
+            .unwrap();
+        let response = handler.handle(request);
+        assert_eq!(StatusCode::OK, response.status());
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            "sha256=oeUF6Wxqoezggrue+wbIDxKRPSF6esKwizR2MHh9HaA=",
+            body["response_token"]
+        );
+
+        let body = Bytes::from_static(br#"{"for_user_id":"1"}"#);
+        let request = Request::post("/webhook")
+            .header(SIGNATURE_HEADER, "sha256=invalid")
+            .body(body.clone())
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, handler.handle(request).status());
+        let request = Request::post("/webhook")
+            .header(
+                SIGNATURE_HEADER,
+                "sha256=GQ7bNWj95QexRX3kxMvQQuaSHYOR68bC4OHSy3cJDrY=",
+            )
+            .body(body)
+            .unwrap();
+        assert_eq!(StatusCode::OK, handler.handle(request).status());
+        assert_eq!(vec!["1"], *received.lock().unwrap());
+    }
+
+    #[cfg(feature = "hyper")]
+    #[tokio::test]
+    async fn test_hyper_serve() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(vec![]));
+        let captured = received.clone();
+        let handler = WebhookHandler::new(Webhook::new("secret"), move |payload| {
+            captured.lock().unwrap().push(payload.for_user_id)
+        });
+        tokio::spawn(handler.serve(listener));
+        let client = reqwest::Client::new();
+        let body = r#"{"for_user_id":"1"}"#;
+        let response = client
+            .post(format!("{}/webhook", base))
+            .header(
+                SIGNATURE_HEADER,
+                Webhook::new("secret").signature(body.as_bytes()),
+            )
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(200, response.status().as_u16());
+        assert_eq!(vec!["1"], *received.lock().unwrap());
+    }
+
+    #[cfg(all(feature = "axum", feature = "hyper"))]
+    #[tokio::test]
+    async fn test_axum_router() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let router = WebhookHandler::new(Webhook::new("secret"), |_| {}).into_router("/webhook");
+        let server = hyper::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service());
+        tokio::spawn(server);
+        let response = reqwest::get(format!("{}/webhook?crc_token=challenge", base))
+            .await
+            .unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(
+            "sha256=oeUF6Wxqoezggrue+wbIDxKRPSF6esKwizR2MHh9HaA=",
+            body["response_token"]
+        );
+    }
+}